@@ -0,0 +1,89 @@
+use arboard::{Clipboard, ImageData};
+use enigo::{Direction, Enigo, Keyboard, Settings};
+
+/// Contents of the clipboard, saved to be restored later.
+pub enum SavedClipboard {
+    Text(String),
+    Image(ImageData<'static>),
+    Empty,
+}
+
+/// Save the current clipboard contents. Formats other than text and images
+/// can not be read and are treated as empty.
+pub fn save(clipboard: &mut Clipboard) -> SavedClipboard {
+    if let Ok(text) = clipboard.get_text() {
+        return SavedClipboard::Text(text);
+    }
+
+    if let Ok(image) = clipboard.get_image() {
+        return SavedClipboard::Image(image);
+    }
+
+    SavedClipboard::Empty
+}
+
+/// Put previously saved contents back on the clipboard.
+pub fn restore(clipboard: &mut Clipboard, saved: SavedClipboard) -> Result<(), String> {
+    let result = match saved {
+        SavedClipboard::Text(text) => clipboard.set_text(text),
+        SavedClipboard::Image(image) => clipboard.set_image(image),
+        SavedClipboard::Empty => clipboard.clear(),
+    };
+
+    result.map_err(|error| error.to_string())
+}
+
+/// Press the platform shortcut with the given key, like Ctrl+V or Cmd+V.
+pub fn press_shortcut(enigo: &mut Enigo, key: char) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    let modifier = enigo::Key::Meta;
+
+    #[cfg(not(target_os = "macos"))]
+    let modifier = enigo::Key::Control;
+
+    let map_error = |error: enigo::InputError| format!("{:?}", error);
+
+    enigo.key(modifier, Direction::Press).map_err(map_error)?;
+    let result = enigo.key(enigo::Key::Unicode(key), Direction::Click);
+    enigo.key(modifier, Direction::Release).map_err(map_error)?;
+
+    result.map_err(map_error)
+}
+
+/// The text on the clipboard, without changing it.
+pub fn read_text() -> Option<String> {
+    // Reading text leaves the clipboard as it is. Images and other formats
+    // fail to read as text and are skipped.
+    match Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+        Ok(text) => Some(text),
+        Err(error) => {
+            println!("Error reading clipboard: {:?}", error);
+            None
+        }
+    }
+}
+
+/// Time the focused application gets to put the selection on the clipboard.
+const COPY_DELAY: u64 = 150;
+
+/// Copy the selection and put the previous clipboard contents back. The
+/// clipboard is cleared first, so an empty selection is not mistaken for
+/// what was on the clipboard before.
+pub fn copy_selection() -> Result<Option<String>, String> {
+    let mut clipboard = Clipboard::new().map_err(|error| error.to_string())?;
+    let saved = save(&mut clipboard);
+
+    clipboard.clear().map_err(|error| error.to_string())?;
+
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|error| format!("{:?}", error))?;
+    let copied = press_shortcut(&mut enigo, 'c');
+
+    std::thread::sleep(std::time::Duration::from_millis(COPY_DELAY));
+
+    let selection = clipboard.get_text().ok().filter(|text| !text.is_empty());
+
+    restore(&mut clipboard, saved)?;
+    copied?;
+
+    Ok(selection)
+}
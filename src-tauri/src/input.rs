@@ -0,0 +1,277 @@
+#[cfg(target_os = "linux")]
+use crate::{keymap, output};
+
+use std::collections::HashSet;
+
+use typls_core::{InputBackend, InputSettings, InputSource, Key, KeyEvent, ReplaySource};
+
+/// Create the input source configured in the settings.
+pub fn create_input_source(settings: &InputSettings) -> Box<dyn InputSource> {
+    match settings.backend {
+        InputBackend::Auto => {
+            // Without access to a keyboard evdev can not read anything, while
+            // rdev at least works for X11 apps under XWayland.
+            #[cfg(target_os = "linux")]
+            if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                if keyboards().next().is_some() {
+                    return evdev_source();
+                }
+
+                println!("No readable keyboard found in /dev/input, using rdev");
+            }
+
+            Box::new(RdevSource)
+        }
+        InputBackend::Rdev => Box::new(RdevSource),
+        InputBackend::Evdev => evdev_source(),
+        InputBackend::Replay => {
+            let path = settings.replay_file.clone().unwrap_or_default();
+
+            match ReplaySource::from_file(&path) {
+                Ok(source) => Box::new(source),
+                Err(error) => {
+                    println!("Error loading replay file {:?}: {}", path, error);
+                    Box::new(ReplaySource::default())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn evdev_source() -> Box<dyn InputSource> {
+    Box::new(EvdevSource)
+}
+
+/// The keyboards in `/dev/input` the user can read, without the virtual
+/// keyboard of the uinput output, whose key presses are typls' own output.
+#[cfg(target_os = "linux")]
+fn keyboards() -> impl Iterator<Item = (std::path::PathBuf, evdev::Device)> {
+    use evdev::Key as EvKey;
+
+    evdev::enumerate().filter(|(_, device)| {
+        device.name() != Some(output::UINPUT_DEVICE_NAME)
+            && device
+                .supported_keys()
+                .is_some_and(|keys| keys.contains(EvKey::KEY_A) && keys.contains(EvKey::KEY_ENTER))
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn evdev_source() -> Box<dyn InputSource> {
+    println!("The evdev input backend is only available on Linux, using rdev");
+    Box::new(RdevSource)
+}
+
+/// Global keyboard hook via rdev. Works on Windows, macOS and X11.
+pub struct RdevSource;
+
+impl InputSource for RdevSource {
+    fn listen(&mut self, mut callback: Box<dyn FnMut(KeyEvent)>) -> Result<(), String> {
+        let mut held = HashSet::new();
+
+        rdev::listen(move |event| {
+            let key = match event.event_type {
+                rdev::EventType::KeyPress(key) => key,
+                rdev::EventType::KeyRelease(key) => {
+                    held.remove(&key);
+                    return;
+                }
+                _ => return,
+            };
+
+            held.insert(key);
+
+            // Keys pressed together with control, alt or meta are shortcuts
+            // and type nothing. AltGr is control and alt on Windows, but
+            // types text.
+            let is_shortcut = !held.contains(&rdev::Key::AltGr)
+                && held.iter().any(|key| {
+                    matches!(
+                        key,
+                        rdev::Key::ControlLeft
+                            | rdev::Key::ControlRight
+                            | rdev::Key::Alt
+                            | rdev::Key::MetaLeft
+                            | rdev::Key::MetaRight
+                    )
+                });
+
+            let key = match key {
+                rdev::Key::Return => Key::Return,
+                rdev::Key::LeftArrow => Key::LeftArrow,
+                rdev::Key::RightArrow => Key::RightArrow,
+                rdev::Key::Escape => Key::Escape,
+                rdev::Key::Backspace => Key::Backspace,
+                rdev::Key::Tab => Key::Tab,
+                rdev::Key::F1 => Key::Function(1),
+                rdev::Key::F2 => Key::Function(2),
+                rdev::Key::F3 => Key::Function(3),
+                rdev::Key::F4 => Key::Function(4),
+                rdev::Key::F5 => Key::Function(5),
+                rdev::Key::F6 => Key::Function(6),
+                rdev::Key::F7 => Key::Function(7),
+                rdev::Key::F8 => Key::Function(8),
+                rdev::Key::F9 => Key::Function(9),
+                rdev::Key::F10 => Key::Function(10),
+                rdev::Key::F11 => Key::Function(11),
+                rdev::Key::F12 => Key::Function(12),
+                rdev::Key::ShiftLeft
+                | rdev::Key::ShiftRight
+                | rdev::Key::ControlLeft
+                | rdev::Key::ControlRight
+                | rdev::Key::Alt
+                | rdev::Key::AltGr
+                | rdev::Key::MetaLeft
+                | rdev::Key::MetaRight => Key::Modifier,
+                _ => Key::Other,
+            };
+
+            callback(KeyEvent {
+                key,
+                name: event.name.filter(|_| !is_shortcut),
+            });
+        })
+        .map_err(|error| format!("{:?}", error))
+    }
+}
+
+/// Reads key presses directly from the keyboards in `/dev/input`, which also
+/// works on Wayland. The user needs read access to the devices, usually by
+/// being in the `input` group.
+///
+/// evdev only reports key codes, so text is derived from a US keyboard layout
+/// and the state of shift and caps lock. Shortcuts produce no text.
+#[cfg(target_os = "linux")]
+pub struct EvdevSource;
+
+#[cfg(target_os = "linux")]
+impl InputSource for EvdevSource {
+    fn listen(&mut self, mut callback: Box<dyn FnMut(KeyEvent)>) -> Result<(), String> {
+        use evdev::Key as EvKey;
+
+        let (tx, rx) = std::sync::mpsc::channel::<(EvKey, i32)>();
+
+        let mut keyboard_count = 0;
+        // Caps lock is shared by all keyboards and may already be on.
+        let mut caps_lock = false;
+
+        // Devices are read blocking, so every keyboard gets its own thread.
+        for (path, mut device) in keyboards() {
+            keyboard_count += 1;
+            caps_lock |= device
+                .get_led_state()
+                .is_ok_and(|leds| leds.contains(evdev::LedType::LED_CAPSL));
+            let tx = tx.clone();
+
+            std::thread::spawn(move || loop {
+                match device.fetch_events() {
+                    Ok(events) => {
+                        for event in events {
+                            if let evdev::InputEventKind::Key(key) = event.kind() {
+                                if tx.send((key, event.value())).is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                    Err(error) => {
+                        println!("Error reading {:?}: {:?}", path, error);
+                        return;
+                    }
+                }
+            });
+        }
+
+        drop(tx);
+
+        if keyboard_count == 0 {
+            return Err(
+                "No readable keyboard found in /dev/input. Is the user in the input group?"
+                    .to_string(),
+            );
+        }
+
+        let mut held = HashSet::new();
+
+        // Values are 0 for release, 1 for press and 2 for auto repeat.
+        for (key, value) in rx {
+            let is_modifier = matches!(
+                key,
+                EvKey::KEY_LEFTSHIFT
+                    | EvKey::KEY_RIGHTSHIFT
+                    | EvKey::KEY_LEFTCTRL
+                    | EvKey::KEY_RIGHTCTRL
+                    | EvKey::KEY_LEFTALT
+                    | EvKey::KEY_RIGHTALT
+                    | EvKey::KEY_LEFTMETA
+                    | EvKey::KEY_RIGHTMETA
+            );
+
+            if is_modifier && value == 0 {
+                held.remove(&key);
+            } else if is_modifier {
+                held.insert(key);
+            }
+
+            if key == EvKey::KEY_CAPSLOCK && value == 1 {
+                caps_lock = !caps_lock;
+            }
+
+            if value == 0 || key == EvKey::KEY_LEFTSHIFT || key == EvKey::KEY_RIGHTSHIFT {
+                continue;
+            }
+
+            let shift =
+                held.contains(&EvKey::KEY_LEFTSHIFT) || held.contains(&EvKey::KEY_RIGHTSHIFT);
+            // Keys pressed together with control, alt or meta are shortcuts
+            // and type nothing.
+            let is_shortcut = held
+                .iter()
+                .any(|&key| key != EvKey::KEY_LEFTSHIFT && key != EvKey::KEY_RIGHTSHIFT);
+            // Caps lock only affects letters, and shift reverts it.
+            let is_letter = keymap::key_text(key, false)
+                .is_some_and(|text| text.chars().all(|c| c.is_ascii_lowercase()));
+
+            let event = match key {
+                EvKey::KEY_ENTER | EvKey::KEY_KPENTER => KeyEvent::key(Key::Return),
+                EvKey::KEY_LEFT => KeyEvent::key(Key::LeftArrow),
+                EvKey::KEY_RIGHT => KeyEvent::key(Key::RightArrow),
+                EvKey::KEY_ESC => KeyEvent::key(Key::Escape),
+                EvKey::KEY_BACKSPACE => KeyEvent::key(Key::Backspace),
+                EvKey::KEY_TAB => KeyEvent {
+                    key: Key::Tab,
+                    name: (!is_shortcut).then(|| "\t".to_string()),
+                },
+                EvKey::KEY_F1 => KeyEvent::key(Key::Function(1)),
+                EvKey::KEY_F2 => KeyEvent::key(Key::Function(2)),
+                EvKey::KEY_F3 => KeyEvent::key(Key::Function(3)),
+                EvKey::KEY_F4 => KeyEvent::key(Key::Function(4)),
+                EvKey::KEY_F5 => KeyEvent::key(Key::Function(5)),
+                EvKey::KEY_F6 => KeyEvent::key(Key::Function(6)),
+                EvKey::KEY_F7 => KeyEvent::key(Key::Function(7)),
+                EvKey::KEY_F8 => KeyEvent::key(Key::Function(8)),
+                EvKey::KEY_F9 => KeyEvent::key(Key::Function(9)),
+                EvKey::KEY_F10 => KeyEvent::key(Key::Function(10)),
+                EvKey::KEY_F11 => KeyEvent::key(Key::Function(11)),
+                EvKey::KEY_F12 => KeyEvent::key(Key::Function(12)),
+                EvKey::KEY_LEFTCTRL
+                | EvKey::KEY_RIGHTCTRL
+                | EvKey::KEY_LEFTALT
+                | EvKey::KEY_RIGHTALT
+                | EvKey::KEY_LEFTMETA
+                | EvKey::KEY_RIGHTMETA => KeyEvent::key(Key::Modifier),
+                _ => KeyEvent {
+                    key: Key::Other,
+                    name: keymap::key_text(key, shift != (caps_lock && is_letter))
+                        .filter(|_| !is_shortcut)
+                        .map(|s| s.to_string()),
+                },
+            };
+
+            callback(event);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use keyring::Entry;
+
+/// Service the secrets of typls are stored under.
+const SERVICE: &str = "typls";
+
+/// Read a secret from the keychain of the OS. `None` if it was never written.
+pub fn read(name: &str) -> Result<Option<String>, String> {
+    let entry = Entry::new(SERVICE, name).map_err(|error| error.to_string())?;
+
+    match entry.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Write a secret to the keychain of the OS, replacing the previous one.
+pub fn write(name: &str, secret: &str) -> Result<(), String> {
+    Entry::new(SERVICE, name)
+        .and_then(|entry| entry.set_password(secret))
+        .map_err(|error| error.to_string())
+}
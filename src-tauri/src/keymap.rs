@@ -0,0 +1,83 @@
+use evdev::Key;
+
+/// Keys of a US keyboard layout and the text they produce without and with
+/// shift. evdev only knows key codes, so this is used to translate between
+/// key codes and text.
+const US_LAYOUT: &[(Key, &str, &str)] = &[
+    (Key::KEY_A, "a", "A"),
+    (Key::KEY_B, "b", "B"),
+    (Key::KEY_C, "c", "C"),
+    (Key::KEY_D, "d", "D"),
+    (Key::KEY_E, "e", "E"),
+    (Key::KEY_F, "f", "F"),
+    (Key::KEY_G, "g", "G"),
+    (Key::KEY_H, "h", "H"),
+    (Key::KEY_I, "i", "I"),
+    (Key::KEY_J, "j", "J"),
+    (Key::KEY_K, "k", "K"),
+    (Key::KEY_L, "l", "L"),
+    (Key::KEY_M, "m", "M"),
+    (Key::KEY_N, "n", "N"),
+    (Key::KEY_O, "o", "O"),
+    (Key::KEY_P, "p", "P"),
+    (Key::KEY_Q, "q", "Q"),
+    (Key::KEY_R, "r", "R"),
+    (Key::KEY_S, "s", "S"),
+    (Key::KEY_T, "t", "T"),
+    (Key::KEY_U, "u", "U"),
+    (Key::KEY_V, "v", "V"),
+    (Key::KEY_W, "w", "W"),
+    (Key::KEY_X, "x", "X"),
+    (Key::KEY_Y, "y", "Y"),
+    (Key::KEY_Z, "z", "Z"),
+    (Key::KEY_1, "1", "!"),
+    (Key::KEY_2, "2", "@"),
+    (Key::KEY_3, "3", "#"),
+    (Key::KEY_4, "4", "$"),
+    (Key::KEY_5, "5", "%"),
+    (Key::KEY_6, "6", "^"),
+    (Key::KEY_7, "7", "&"),
+    (Key::KEY_8, "8", "*"),
+    (Key::KEY_9, "9", "("),
+    (Key::KEY_0, "0", ")"),
+    (Key::KEY_MINUS, "-", "_"),
+    (Key::KEY_EQUAL, "=", "+"),
+    (Key::KEY_LEFTBRACE, "[", "{"),
+    (Key::KEY_RIGHTBRACE, "]", "}"),
+    (Key::KEY_SEMICOLON, ";", ":"),
+    (Key::KEY_APOSTROPHE, "'", "\""),
+    (Key::KEY_GRAVE, "`", "~"),
+    (Key::KEY_BACKSLASH, "\\", "|"),
+    (Key::KEY_COMMA, ",", "<"),
+    (Key::KEY_DOT, ".", ">"),
+    (Key::KEY_SLASH, "/", "?"),
+    (Key::KEY_SPACE, " ", " "),
+    (Key::KEY_TAB, "\t", "\t"),
+    (Key::KEY_ENTER, "\n", "\n"),
+];
+
+/// Text produced by a key, if any.
+pub fn key_text(key: Key, shift: bool) -> Option<&'static str> {
+    US_LAYOUT
+        .iter()
+        .find(|(k, _, _)| *k == key)
+        .map(|(_, normal, shifted)| if shift { *shifted } else { *normal })
+}
+
+/// The key (and whether shift is needed) that produces the given text.
+pub fn text_key(text: &str) -> Option<(Key, bool)> {
+    US_LAYOUT.iter().find_map(|(key, normal, shifted)| {
+        if *normal == text {
+            Some((*key, false))
+        } else if *shifted == text {
+            Some((*key, true))
+        } else {
+            None
+        }
+    })
+}
+
+/// All keys of the layout.
+pub fn layout_keys() -> impl Iterator<Item = Key> {
+    US_LAYOUT.iter().map(|(key, _, _)| *key)
+}
@@ -1,89 +1,91 @@
+mod clipboard;
+mod input;
+mod keychain;
+#[cfg(target_os = "linux")]
+mod keymap;
+mod output;
+mod system;
+
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
-use enigo::{Enigo, Keyboard, Settings};
-use rdev::{listen, EventType, Key};
-
-use serde::{Deserialize, Serialize};
-use tauri::Manager;
+use tauri::{Emitter, Manager, WebviewUrl, WebviewWindowBuilder, WindowEvent};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
 
 use active_win_pos_rs::{get_active_window, ActiveWindow};
 use std::time::Duration;
 
-use uuid::Uuid;
+use typls_core::{
+    default_settings, ActiveApp, AppSettings, Capture, CaptureSignal, CaretMove, Expansion,
+    ExpansionContext, ExpansionPlan, History, HistoryEntry, HistoryMode, HistorySettings,
+    InjectionGuard, LastExpansion, OutputKind, OutputSink, PhraseAnalyzer, PhraseSuggestion,
+    SessionUpdate, ShellPermissions, Signal, SnippetSession, StatsSummary, UndoPlan, UndoWindow,
+    UsageStats, VariableForm,
+};
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
-#[serde(rename_all = "camelCase")]
-struct AppSettings {
-    trigger: TriggerSettings,
-    confirm: ConfirmSettings,
-    variables: VariableSettings,
-    expansions: Vec<Expansion>,
-    groups: Option<Vec<Group>>,
-    active_group: Option<String>,
+struct AppState {
+    settings: Arc<std::sync::RwLock<AppSettings>>,
+    /// Set while typls types an expansion, so its own key presses are not captured.
+    injection_guard: InjectionGuard,
+    /// Tab stops of the last expansion that were not visited yet.
+    snippet_session: Mutex<Option<SnippetSession>>,
+    /// Lets the key press right after an expansion undo it.
+    undo_window: Mutex<UndoWindow>,
+    history: Mutex<History>,
+    stats: Mutex<UsageStats>,
+    phrases: Mutex<PhraseAnalyzer>,
+    /// Whether the phrase counts may be saved, which needs their salt to be
+    /// kept in the keychain.
+    save_phrases: AtomicBool,
+    /// Commands `{shell:...}` variables may run.
+    shell_permissions: Mutex<ShellPermissions>,
+    /// The capture whose variables are asked for in the form window.
+    pending_form: Mutex<Option<(CaptureSignal, VariableForm)>>,
+    /// Lets commands send work to the executor thread.
+    signals: std::sync::mpsc::Sender<Signal>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
-#[serde(rename_all = "camelCase")]
-struct TriggerSettings {
-    string: String,
-}
+#[cfg(dev)]
+const SETTINGS_FILE_NAME: &str = "test.json";
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
-#[serde(rename_all = "camelCase")]
-struct ConfirmSettings {
-    chars: Vec<String>,
-    key_enter: bool,
-    key_right_arrow: bool,
-    append: bool,
-    auto: bool,
-}
+#[cfg(not(dev))]
+const SETTINGS_FILE_NAME: &str = "settings.json";
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
-#[serde(rename_all = "camelCase")]
-struct VariableSettings {
-    separator: String,
-}
+#[cfg(dev)]
+const HISTORY_FILE_NAME: &str = "test-history.json";
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
-#[serde(rename_all = "camelCase")]
-struct Expansion {
-    #[serde(default = "generate_uuid")]
-    id: String,
-    abbr: String,
-    text: String,
-    group: Option<String>,
-}
+#[cfg(not(dev))]
+const HISTORY_FILE_NAME: &str = "history.json";
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
-#[serde(rename_all = "camelCase")]
-struct Group {
-    id: String,
-    name: String,
-    apps: Vec<App>,
-}
+#[cfg(dev)]
+const STATS_FILE_NAME: &str = "test-stats.json";
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
-#[serde(rename_all = "camelCase")]
-struct App {
-    path: String,
-    os: String,
-}
+#[cfg(not(dev))]
+const STATS_FILE_NAME: &str = "stats.json";
 
-struct AppState {
-    settings: Arc<std::sync::RwLock<AppSettings>>,
-}
+#[cfg(dev)]
+const PHRASES_FILE_NAME: &str = "test-phrases.json";
+
+#[cfg(not(dev))]
+const PHRASES_FILE_NAME: &str = "phrases.json";
 
 #[cfg(dev)]
-const SETTINGS_FILE_NAME: &str = "test.json";
+const PHRASES_SALT_NAME: &str = "test-phrases-salt";
 
 #[cfg(not(dev))]
-const SETTINGS_FILE_NAME: &str = "settings.json";
+const PHRASES_SALT_NAME: &str = "phrases-salt";
 
-fn generate_uuid() -> String {
-    Uuid::new_v4().to_string()
-}
+#[cfg(dev)]
+const SHELL_PERMISSIONS_FILE_NAME: &str = "test-shell.json";
+
+#[cfg(not(dev))]
+const SHELL_PERMISSIONS_FILE_NAME: &str = "shell.json";
+
+/// Label of the popup window that asks for the values of variables.
+const FORM_WINDOW_LABEL: &str = "form";
 
 #[tauri::command]
 fn get_settings(state: tauri::State<'_, AppState>) -> Result<AppSettings, String> {
@@ -99,7 +101,15 @@ fn set_settings(
     settings: AppSettings,
 ) -> Result<(), String> {
     print!("save_settings: {:?}", settings);
+    if let Some(hotkey) = settings.selection.invalid_hotkeys().next() {
+        return Err(format!(
+            "Only function keys can be selection hotkeys, not {:?}",
+            hotkey.key
+        ));
+    }
+
     let mut app_settings = state.settings.write().unwrap();
+    let suggestions_were_enabled = app_settings.suggestions.enabled;
     *app_settings = settings;
 
     let app_config_dir = app.path().app_config_dir().unwrap();
@@ -110,6 +120,17 @@ fn set_settings(
     println!("Settings file path: {:?}", setting_file_path);
     std::fs::write(setting_file_path, default_settings_json).unwrap();
 
+    apply_history_settings(&app, &app_settings.history);
+
+    let suggestions_enabled = app_settings.suggestions.enabled;
+    drop(app_settings);
+
+    if !suggestions_enabled {
+        forget_phrases(&app);
+    } else if !suggestions_were_enabled {
+        load_phrases(&app);
+    }
+
     Ok(())
 }
 
@@ -118,6 +139,181 @@ fn get_default_settings() -> Result<AppSettings, String> {
     Ok(default_settings())
 }
 
+#[tauri::command]
+fn get_history(state: tauri::State<'_, AppState>) -> Result<Vec<HistoryEntry>, String> {
+    Ok(state.history.lock().unwrap().entries())
+}
+
+#[tauri::command]
+fn search_history(
+    state: tauri::State<'_, AppState>,
+    query: String,
+) -> Result<Vec<HistoryEntry>, String> {
+    Ok(state.history.lock().unwrap().search(&query))
+}
+
+#[tauri::command]
+fn clear_history(app: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state.history.lock().unwrap().clear();
+
+    let history_file_path = get_settings_directory_path(&app).join(HISTORY_FILE_NAME);
+    if history_file_path.exists() {
+        std::fs::remove_file(history_file_path).map_err(|error| error.to_string())?;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+fn reinsert_history_entry(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let plan = state
+        .history
+        .lock()
+        .unwrap()
+        .get(&id)
+        .map(|entry| entry.plan())
+        .ok_or("History entry not found")?;
+
+    // Give the focus back to the app the text should be typed into.
+    if let Some(window) = app.get_webview_window("main") {
+        window.minimize().map_err(|error| error.to_string())?;
+    }
+
+    state
+        .signals
+        .send(Signal::Insert(plan))
+        .map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+fn get_stats(state: tauri::State<'_, AppState>) -> Result<StatsSummary, String> {
+    let app_settings = state.settings.read().unwrap();
+    Ok(state.stats.lock().unwrap().summary(&app_settings))
+}
+
+#[tauri::command]
+fn get_unused_expansions(state: tauri::State<'_, AppState>) -> Result<Vec<Expansion>, String> {
+    let app_settings = state.settings.read().unwrap();
+    let stats = state.stats.lock().unwrap();
+
+    Ok(stats.unused(&app_settings).into_iter().cloned().collect())
+}
+
+#[tauri::command]
+fn get_phrase_suggestions(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<PhraseSuggestion>, String> {
+    let app_settings = state.settings.read().unwrap();
+    let phrases = state.phrases.lock().unwrap();
+
+    Ok(phrases.suggestions(&app_settings, app_settings.suggestions.min_count))
+}
+
+#[tauri::command]
+fn get_shell_permissions(state: tauri::State<'_, AppState>) -> Result<ShellPermissions, String> {
+    Ok(state.shell_permissions.lock().unwrap().clone())
+}
+
+/// Allow a command for all expansions or only the given one. The user has to
+/// confirm it in a native dialog, so neither the settings file nor the web
+/// view can allow commands on their own.
+#[tauri::command]
+async fn allow_shell_command(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    command: String,
+    expansion_id: Option<String>,
+) -> Result<bool, String> {
+    let scope = match &expansion_id {
+        Some(_) => "this expansion",
+        None => "all expansions",
+    };
+
+    let confirmed = app
+        .dialog()
+        .message(format!(
+            "Allow {} to run the following command?\n\n{}",
+            scope, command
+        ))
+        .title("Allow shell command")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Allow".to_string(),
+            "Cancel".to_string(),
+        ))
+        .blocking_show();
+
+    if !confirmed {
+        return Ok(false);
+    }
+
+    let mut permissions = state.shell_permissions.lock().unwrap();
+    permissions.allow(&command, expansion_id.as_deref());
+    permissions.save(get_settings_directory_path(&app).join(SHELL_PERMISSIONS_FILE_NAME))?;
+
+    Ok(true)
+}
+
+#[tauri::command]
+fn revoke_shell_command(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    command: String,
+    expansion_id: Option<String>,
+) -> Result<(), String> {
+    let mut permissions = state.shell_permissions.lock().unwrap();
+    permissions.revoke(&command, expansion_id.as_deref());
+    permissions.save(get_settings_directory_path(&app).join(SHELL_PERMISSIONS_FILE_NAME))
+}
+
+#[tauri::command]
+fn get_variable_form(state: tauri::State<'_, AppState>) -> Result<Option<VariableForm>, String> {
+    Ok(state
+        .pending_form
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|(_, form)| form.clone()))
+}
+
+/// Expand the pending capture with the values of the form fields, in the
+/// order of the fields.
+#[tauri::command]
+fn submit_variable_form(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    values: Vec<String>,
+) -> Result<(), String> {
+    let (signal, form) = state
+        .pending_form
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or("No variable form is open")?;
+
+    // Closing the form gives the focus back to the app the text should be
+    // typed into.
+    close_variable_form(&app)?;
+
+    state
+        .signals
+        .send(Signal::Form(signal, form.fill(values)))
+        .map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+fn cancel_variable_form(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    *state.pending_form.lock().unwrap() = None;
+    close_variable_form(&app)
+}
+
 #[tauri::command]
 fn open_settings_dir(app: tauri::AppHandle) {
     let app_config_dir = get_settings_directory_path(&app);
@@ -133,43 +329,6 @@ fn open_settings_dir(app: tauri::AppHandle) {
     }
 }
 
-const DEFAULT_CONFIRM_CHARS: [&str; 7] = [" ", ".", ";", "!", "?", ":", ","];
-
-fn default_settings() -> AppSettings {
-    AppSettings {
-        trigger: TriggerSettings {
-            string: "'".to_string(),
-        },
-        confirm: ConfirmSettings {
-            chars: DEFAULT_CONFIRM_CHARS
-                .iter()
-                .map(|&s| s.to_string())
-                .collect(),
-            key_enter: true,
-            key_right_arrow: true,
-            append: true,
-            auto: false,
-        },
-        variables: VariableSettings {
-            separator: "|".to_string(),
-        },
-        expansions: vec![Expansion {
-            id: "typls".to_string(),
-            abbr: "typls".to_string(),
-            text: "Type less with typls: https://typls.app".to_string(),
-            group: None,
-        }],
-        groups: Some(vec![]),
-        active_group: None,
-    }
-}
-
-struct CaptureSignal {
-    sequence: String,
-    append: String,
-    append_enter: bool,
-}
-
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let default_app_settings = default_settings();
@@ -193,7 +352,7 @@ pub fn run() {
     });
 
     // Channel to communicate the captured sequence and possibly trigger an expansion.
-    let (tx, rx) = std::sync::mpsc::channel::<CaptureSignal>();
+    let (tx, rx) = std::sync::mpsc::channel::<Signal>();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_os::init())
@@ -201,6 +360,16 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .manage(AppState {
             settings: Arc::new(std::sync::RwLock::new(default_app_settings)),
+            injection_guard: InjectionGuard::default(),
+            snippet_session: Mutex::new(None),
+            undo_window: Mutex::new(UndoWindow::new()),
+            history: Mutex::new(History::new()),
+            stats: Mutex::new(UsageStats::new()),
+            phrases: Mutex::new(PhraseAnalyzer::new()),
+            save_phrases: AtomicBool::new(false),
+            shell_permissions: Mutex::new(ShellPermissions::new()),
+            pending_form: Mutex::new(None),
+            signals: tx.clone(),
         })
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_process::init())
@@ -210,26 +379,194 @@ pub fn run() {
             set_settings,
             get_default_settings,
             open_settings_dir,
+            get_history,
+            search_history,
+            clear_history,
+            reinsert_history_entry,
+            get_stats,
+            get_unused_expansions,
+            get_phrase_suggestions,
+            get_shell_permissions,
+            allow_shell_command,
+            revoke_shell_command,
+            get_variable_form,
+            submit_variable_form,
+            cancel_variable_form,
         ])
         .setup(|app| {
             load_settings(&app.app_handle());
+            load_history(&app.app_handle());
+            load_stats(&app.app_handle());
+            load_phrases(&app.app_handle());
+            load_shell_permissions(&app.app_handle());
 
             let app_handle = app.app_handle().clone();
 
             thread::spawn(move || {
+                let mut sinks = HashMap::new();
+
                 for received in rx {
                     let app_state = app_handle.state::<AppState>();
                     let app_settings = app_state.settings.read().unwrap();
 
-                    end_capturing(
-                        &received.sequence,
-                        &app_settings.expansions,
-                        &received.append,
-                        received.append_enter,
-                        &app_settings.variables.separator,
-                        &active_window,
-                        &app_settings,
-                    );
+                    match received {
+                        Signal::Expand(signal) => {
+                            if app_settings.variables.form
+                                && open_variable_form(
+                                    &app_handle,
+                                    &signal,
+                                    &active_window,
+                                    &app_settings,
+                                )
+                            {
+                                continue;
+                            }
+
+                            // Copying the selection for `{selection}` presses keys.
+                            let Some(_injection) = app_state.injection_guard.begin() else {
+                                println!("Skipping expansion while another one is being typed");
+                                continue;
+                            };
+
+                            let context =
+                                expansion_context(&app_handle, &active_window, &app_settings);
+                            let plan = typls_core::plan_expansion(&signal, &app_settings, &context);
+                            report_errors(&app_handle, context.take_errors());
+
+                            let Some(plan) = plan else {
+                                continue;
+                            };
+
+                            execute_plan(&plan, &mut sinks);
+
+                            let active_app = get_active_app(&active_window);
+                            let entry =
+                                HistoryEntry::new(&signal, &plan, &app_settings, &active_app);
+
+                            end_expansion(
+                                &app_handle,
+                                &signal,
+                                plan,
+                                entry,
+                                &active_window,
+                                &app_settings,
+                            );
+                        }
+                        Signal::Form(signal, params) => {
+                            // Wait for the focus to move from the form back to the
+                            // app the abbreviation was typed into.
+                            thread::sleep(Duration::from_millis(300));
+
+                            let Some(_injection) = app_state.injection_guard.begin() else {
+                                continue;
+                            };
+
+                            let context =
+                                expansion_context(&app_handle, &active_window, &app_settings);
+                            let plan = typls_core::plan_form(
+                                &signal,
+                                params.clone(),
+                                &app_settings,
+                                &context,
+                            );
+                            report_errors(&app_handle, context.take_errors());
+
+                            let Some(plan) = plan else {
+                                continue;
+                            };
+
+                            execute_plan(&plan, &mut sinks);
+
+                            let active_app = get_active_app(&active_window);
+                            let entry = HistoryEntry {
+                                params,
+                                ..HistoryEntry::new(&signal, &plan, &app_settings, &active_app)
+                            };
+
+                            end_expansion(
+                                &app_handle,
+                                &signal,
+                                plan,
+                                entry,
+                                &active_window,
+                                &app_settings,
+                            );
+                        }
+                        Signal::MoveCaret(caret_move) => {
+                            let Some(_injection) = app_state.injection_guard.begin() else {
+                                continue;
+                            };
+
+                            move_caret(&caret_move, &mut sinks);
+                        }
+                        Signal::Undo(undo) => {
+                            // The focus changed, so the expansion is not where
+                            // the caret is anymore.
+                            if active_window.lock().unwrap().window_id != undo.window {
+                                continue;
+                            }
+
+                            let Some(_injection) = app_state.injection_guard.begin() else {
+                                continue;
+                            };
+
+                            undo_expansion(&undo, &mut sinks);
+                        }
+                        Signal::Insert(plan) => {
+                            // Wait for the focus to move from the typls window back
+                            // to the previous app.
+                            thread::sleep(Duration::from_millis(300));
+
+                            let Some(_injection) = app_state.injection_guard.begin() else {
+                                continue;
+                            };
+
+                            execute_plan(&plan, &mut sinks);
+                        }
+                        Signal::Selection(hotkey) => {
+                            let Some(_injection) = app_state.injection_guard.begin() else {
+                                continue;
+                            };
+
+                            let active_app = get_active_app(&active_window);
+                            let context = ExpansionContext::new(active_app.clone())
+                                .with_system(Arc::new(system::SystemReader))
+                                .with_shell(
+                                    app_state.shell_permissions.lock().unwrap().clone(),
+                                    app_settings.shell.clone(),
+                                );
+
+                            let plan = typls_core::plan_selection(
+                                &hotkey.sequence,
+                                &app_settings,
+                                &context,
+                            );
+                            report_errors(&app_handle, context.take_errors());
+
+                            let Some(plan) = plan else {
+                                println!("No expansion for selection hotkey: {}", hotkey.sequence);
+                                continue;
+                            };
+
+                            execute_plan(&plan, &mut sinks);
+
+                            let signal = CaptureSignal {
+                                sequence: hotkey.sequence,
+                                append: String::new(),
+                                append_enter: false,
+                            };
+
+                            record_history(
+                                &app_handle,
+                                &app_settings.history,
+                                HistoryEntry::new(&signal, &plan, &app_settings, &active_app),
+                            );
+                            record_stats(&app_handle, &signal, &plan, &active_app);
+
+                            *app_state.snippet_session.lock().unwrap() = plan.snippet;
+                        }
+                        Signal::SavePhrases => save_phrases(&app_handle),
+                    }
                 }
             });
 
@@ -277,7 +614,7 @@ fn load_settings(app: &tauri::AppHandle) {
         let settings_json = std::fs::read_to_string(setting_file_path).unwrap();
 
         // parse settings and catch errors
-        let new_settings: AppSettings = match serde_json::from_str(&settings_json) {
+        let mut new_settings: AppSettings = match serde_json::from_str(&settings_json) {
             Ok(settings) => settings,
             Err(error) => {
                 println!("Error parsing settings: {:?}", error);
@@ -285,6 +622,13 @@ fn load_settings(app: &tauri::AppHandle) {
             }
         };
 
+        for hotkey in new_settings.selection.remove_invalid_hotkeys() {
+            println!(
+                "Ignoring selection hotkey on {:?}: only function keys can be hotkeys",
+                hotkey.key
+            );
+        }
+
         // Write new settings into app state.
         let app_state = app.state::<AppState>();
         let mut app_settings = app_state.settings.write().unwrap();
@@ -292,388 +636,452 @@ fn load_settings(app: &tauri::AppHandle) {
     }
 }
 
-fn handle_input(app: &tauri::AppHandle, tx: std::sync::mpsc::Sender<CaptureSignal>) {
-    let mut current_sequence = String::new();
-    let mut is_capturing = false;
+fn load_history(app: &tauri::AppHandle) {
+    let app_state = app.state::<AppState>();
+    let history_settings = app_state.settings.read().unwrap().history.clone();
 
-    let app_handle_ = app.clone();
+    let history_file_path = get_settings_directory_path(app).join(HISTORY_FILE_NAME);
 
-    if let Err(error) = listen(move |event| {
-        let app_state = app_handle_.state::<AppState>();
-        let app_settings = app_state.settings.read().unwrap();
+    if history_settings.mode == HistoryMode::File && history_file_path.exists() {
+        match History::from_file(&history_file_path) {
+            Ok(history) => *app_state.history.lock().unwrap() = history,
+            Err(error) => println!("Error loading history: {}", error),
+        }
+    }
 
-        let mut return_early = false;
+    apply_history_settings(app, &history_settings);
+}
 
-        if app_settings.trigger.string.is_empty() {
-            return;
-        }
+/// Bring the history in line with the privacy settings. The history file is
+/// removed as soon as the history should not be kept on disk anymore.
+fn apply_history_settings(app: &tauri::AppHandle, settings: &HistorySettings) {
+    let app_state = app.state::<AppState>();
+    let mut history = app_state.history.lock().unwrap();
 
-        match event.event_type {
-            // Confirm capture without appending anything.
-            EventType::KeyPress(Key::RightArrow) | EventType::KeyPress(Key::Return) => {
-                if !is_capturing {
-                    return;
-                }
+    if settings.mode == HistoryMode::Off {
+        history.clear();
+    }
 
-                // Return if confirm via the pressed key is disabled.
-                if (!app_settings.confirm.key_right_arrow
-                    && event.event_type == EventType::KeyPress(Key::RightArrow))
-                    || (!app_settings.confirm.key_enter
-                        && event.event_type == EventType::KeyPress(Key::Return))
-                {
-                    return;
-                }
+    history.truncate(settings.limit);
 
-                let is_return_key = event.event_type == EventType::KeyPress(Key::Return);
+    let history_file_path = get_settings_directory_path(app).join(HISTORY_FILE_NAME);
 
-                tx.send(CaptureSignal {
-                    sequence: current_sequence.clone(),
-                    append: "".to_string(),
-                    append_enter: is_return_key,
-                })
-                .unwrap();
+    let result = if settings.mode == HistoryMode::File {
+        history.save(&history_file_path)
+    } else if history_file_path.exists() {
+        std::fs::remove_file(&history_file_path).map_err(|error| error.to_string())
+    } else {
+        Ok(())
+    };
 
-                is_capturing = false;
-                current_sequence = String::new();
-                return_early = true;
-            }
-            // Cancel capture.
-            EventType::KeyPress(Key::Escape) => {
-                if !is_capturing {
-                    return;
-                }
-                is_capturing = false;
-                current_sequence = String::new();
-                return_early = true;
-            }
-            EventType::KeyPress(Key::Backspace) => {
-                if !is_capturing {
-                    return;
-                }
+    if let Err(error) = result {
+        println!("Error updating history file: {}", error);
+    }
+}
 
-                if current_sequence.is_empty() {
-                    is_capturing = false;
-                } else {
-                    current_sequence.pop();
-                }
+fn record_history(app: &tauri::AppHandle, settings: &HistorySettings, entry: HistoryEntry) {
+    if settings.mode == HistoryMode::Off {
+        return;
+    }
 
-                return_early = true;
-            }
-            _ => (),
-        }
+    let app_state = app.state::<AppState>();
+    let mut history = app_state.history.lock().unwrap();
+    history.push(entry, settings.limit);
 
-        if return_early {
-            return;
+    if settings.mode == HistoryMode::File {
+        let history_file_path = get_settings_directory_path(app).join(HISTORY_FILE_NAME);
+
+        if let Err(error) = history.save(history_file_path) {
+            println!("Error saving history: {}", error);
         }
+    }
+}
 
-        match event.name {
-            Some(string) => {
-                if string.is_empty() {
-                    return;
-                }
+fn load_stats(app: &tauri::AppHandle) {
+    let stats_file_path = get_settings_directory_path(app).join(STATS_FILE_NAME);
 
-                if is_capturing {
-                    // TODO: Make tab work? string == '\t'
-                    if app_settings.confirm.chars.contains(&string) {
-                        tx.send(CaptureSignal {
-                            sequence: current_sequence.clone(),
-                            append: if app_settings.confirm.append {
-                                string.clone()
-                            } else {
-                                "".to_string()
-                            },
-                            append_enter: false,
-                        })
-                        .unwrap();
-
-                        is_capturing = false;
-                        current_sequence = String::new();
-                    } else {
-                        current_sequence.push_str(&string);
-
-                        if app_settings.confirm.auto {
-                            let matching_expansion = app_settings
-                                .expansions
-                                .iter()
-                                .find(|&e| e.abbr == current_sequence);
-
-                            let expansions_starting_with_sequence = app_settings
-                                .expansions
-                                .iter()
-                                .filter(|&e| e.abbr.starts_with(&current_sequence))
-                                .count();
-
-                            if matching_expansion.is_some()
-                                && expansions_starting_with_sequence == 1
-                            {
-                                let loose_variable_regex =
-                                    regex::Regex::new(r"\{[^\s}]*\}").unwrap();
-                                let matching_has_no_variables = !loose_variable_regex
-                                    .is_match(&matching_expansion.unwrap().text);
-
-                                if matching_has_no_variables {
-                                    tx.send(CaptureSignal {
-                                        sequence: current_sequence.clone(),
-                                        append: "".to_string(),
-                                        append_enter: false,
-                                    })
-                                    .unwrap();
-
-                                    is_capturing = false;
-                                    current_sequence = String::new();
-                                    return;
-                                }
-                            }
-                        }
-                    }
-                } else {
-                    if string == app_settings.trigger.string {
-                        current_sequence = String::new();
-                        is_capturing = true;
-                    }
-                }
-            }
-            None => (),
-        }
-    }) {
-        println!("Error: {:?}", error)
+    if !stats_file_path.exists() {
+        return;
+    }
+
+    match UsageStats::from_file(&stats_file_path) {
+        Ok(stats) => *app.state::<AppState>().stats.lock().unwrap() = stats,
+        Err(error) => println!("Error loading stats: {}", error),
     }
 }
 
-fn end_capturing(
-    current_sequence: &String,
-    expansions: &Vec<Expansion>,
-    append: &str,
-    append_enter: bool,
-    variable_separator: &str,
-    active_window: &Arc<Mutex<ActiveWindow>>,
-    app_settings: &AppSettings,
+fn record_stats(
+    app: &tauri::AppHandle,
+    signal: &CaptureSignal,
+    plan: &ExpansionPlan,
+    active_app: &ActiveApp,
 ) {
-    let parts = current_sequence.split(variable_separator);
+    let app_state = app.state::<AppState>();
+    let mut stats = app_state.stats.lock().unwrap();
+    stats.record(signal, plan, &active_app.process_path);
 
-    // Extract abbreviation (first element).
-    let abbr = parts.clone().next().unwrap();
+    let stats_file_path = get_settings_directory_path(app).join(STATS_FILE_NAME);
 
-    // Find all matching expansions.
-    let mut matching_expansions = expansions
-        .iter()
-        .filter(|&e| e.abbr == abbr.to_string())
-        .collect::<Vec<_>>();
+    if let Err(error) = stats.save(stats_file_path) {
+        println!("Error saving stats: {}", error);
+    }
+}
 
-    if matching_expansions.is_empty() {
+/// The phrase counts are hashed with a salt from the keychain, so it is not
+/// stored next to them. Without a keychain they are only kept in memory.
+fn load_phrases(app: &tauri::AppHandle) {
+    let app_state = app.state::<AppState>();
+
+    if !app_state.settings.read().unwrap().suggestions.enabled {
         return;
     }
 
-    // Sort expansions so the ones assigned to a group are placed first.
-    // This is done so we always check constrained expansions first.
-    matching_expansions.sort_by_key(|e| e.group.is_none());
+    let salt = match keychain::read(PHRASES_SALT_NAME) {
+        Ok(Some(salt)) => salt,
+        // The counts can not be used without their salt.
+        Ok(None) => return forget_phrases(app),
+        Err(error) => {
+            println!("Error reading phrase salt: {}", error);
+            return;
+        }
+    };
 
-    let mut chosen_expansion = None;
+    let phrases_file_path = get_settings_directory_path(app).join(PHRASES_FILE_NAME);
+    let phrases = if phrases_file_path.exists() {
+        PhraseAnalyzer::from_file(&phrases_file_path, &salt).unwrap_or_else(|error| {
+            println!("Error loading phrase counts: {}", error);
+            PhraseAnalyzer::with_salt(&salt)
+        })
+    } else {
+        PhraseAnalyzer::with_salt(&salt)
+    };
 
-    // Select first expansion when active group is set
-    if let Some(active_group) = &app_settings.active_group {
-        let active_group_expansions = matching_expansions
-            .iter()
-            .filter(|&e| e.group.is_none() || e.group == Some(active_group.clone()))
-            .collect::<Vec<_>>();
+    *app_state.phrases.lock().unwrap() = phrases;
+    app_state.save_phrases.store(true, Ordering::SeqCst);
+}
 
-        if !active_group_expansions.is_empty() {
-            chosen_expansion = Some(active_group_expansions[0]);
-        }
+fn load_shell_permissions(app: &tauri::AppHandle) {
+    let permissions_file_path = get_settings_directory_path(app).join(SHELL_PERMISSIONS_FILE_NAME);
+
+    if !permissions_file_path.exists() {
+        return;
     }
 
-    if chosen_expansion.is_none() {
-        // Find expansion by group matching the active window/app.
-        for exp in matching_expansions.iter() {
-            if let Some(group_id) = &exp.group {
-                let window_props = active_window.lock().unwrap();
-                let process_path = &window_props.process_path;
+    match ShellPermissions::from_file(&permissions_file_path) {
+        Ok(permissions) => *app.state::<AppState>().shell_permissions.lock().unwrap() = permissions,
+        Err(error) => println!("Error loading shell permissions: {}", error),
+    }
+}
 
-                // find group in app_settings that has matching id
-                let group = app_settings
-                    .groups
-                    .as_ref()
-                    .unwrap()
-                    .iter()
-                    .find(|&g| g.id == *group_id);
+fn save_phrases(app: &tauri::AppHandle) {
+    let app_state = app.state::<AppState>();
+    let phrases = app_state.phrases.lock().unwrap();
 
-                if group.is_none() {
-                    continue;
-                }
+    if !app_state.save_phrases.load(Ordering::SeqCst) {
+        return;
+    }
 
-                let platform = tauri_plugin_os::platform();
+    let phrases_file_path = get_settings_directory_path(app).join(PHRASES_FILE_NAME);
 
-                if group.unwrap().apps.iter().any(|app| {
-                    app.os == platform && app.path == process_path.to_string_lossy().to_string()
-                }) {
-                    chosen_expansion = Some(exp);
-                    break;
-                }
-            } else {
-                chosen_expansion = Some(exp);
-            }
+    if let Err(error) = phrases.save(phrases_file_path) {
+        println!("Error saving phrase counts: {}", error);
+    }
+}
+
+/// Drop everything the phrase analyzer learned, in memory and on disk, and
+/// start over with a new salt.
+fn forget_phrases(app: &tauri::AppHandle) {
+    let app_state = app.state::<AppState>();
+    let phrases = PhraseAnalyzer::new();
+
+    let saved = match keychain::write(PHRASES_SALT_NAME, phrases.salt()) {
+        Ok(()) => true,
+        Err(error) => {
+            println!("Error saving phrase salt: {}", error);
+            false
+        }
+    };
+
+    *app_state.phrases.lock().unwrap() = phrases;
+    app_state.save_phrases.store(saved, Ordering::SeqCst);
+
+    let phrases_file_path = get_settings_directory_path(app).join(PHRASES_FILE_NAME);
+    if phrases_file_path.exists() {
+        if let Err(error) = std::fs::remove_file(phrases_file_path) {
+            println!("Error removing phrase counts: {:?}", error);
         }
     }
+}
 
-    // Parse variables in expansion text.
-    let mut variables = parse_variables(chosen_expansion.unwrap());
-    variables.unnamed.reverse();
+fn handle_input(app: &tauri::AppHandle, tx: std::sync::mpsc::Sender<Signal>) {
+    let mut capture = Capture::new();
 
-    let mut params_unnamed: Vec<&str> = vec![];
-    let mut params_named: HashMap<String, &str> = HashMap::new();
+    let app_handle_ = app.clone();
 
-    // Extract parameters into named and unnamed.
-    for part in parts.skip(1) {
-        let pair: Vec<&str> = part.split("=").collect();
-        let (key, value) = if pair.len() == 2 {
-            (pair[0], pair[1])
-        } else {
-            ("", pair[0])
-        };
+    let mut source = {
+        let app_state = app.state::<AppState>();
+        let app_settings = app_state.settings.read().unwrap();
+        input::create_input_source(&app_settings.input)
+    };
+
+    if let Err(error) = source.listen(Box::new(move |event| {
+        let app_state = app_handle_.state::<AppState>();
 
-        if key.is_empty() {
-            params_unnamed.push(value);
-        } else {
-            params_named.insert(key.to_string(), value);
+        // Ignore the key presses typls simulates itself. Otherwise expansions
+        // containing the trigger would start capturing their own output.
+        if app_state.injection_guard.is_active() {
+            capture.cancel();
+            return;
         }
-    }
 
-    // Reverse unnamed parameters to replace them in the correct order.
-    params_unnamed.reverse();
+        // Typing into the variable form must not start another expansion.
+        if app_state.pending_form.lock().unwrap().is_some() {
+            capture.cancel();
+            return;
+        }
 
-    let mut text = chosen_expansion.unwrap().text.clone();
+        let app_settings = app_state.settings.read().unwrap();
 
-    // Replace unnamed variables ({}, {=default}) in text with provided or default values.
-    for variable in variables.unnamed.iter_mut() {
-        // Create replace pattern like {} or {=default} and replace first occurrence.
-        let pattern = if variable.default.is_empty() {
-            "{}".to_string()
-        } else {
-            format!("{{={}}}", variable.default)
-        };
+        if app_settings.suggestions.enabled {
+            let mut phrases = app_state.phrases.lock().unwrap();
+            phrases.handle(&event, app_settings.suggestions.min_count);
 
-        match params_unnamed.pop() {
-            Some(value) => {
-                text = text.replacen(&pattern, value, 1);
-            }
-            None => {
-                // Replace with default value if no value was provided.
-                text = text.replacen(&pattern, &variable.default, 1);
+            if phrases.take_save_request() {
+                tx.send(Signal::SavePhrases).unwrap();
             }
         }
-    }
 
-    // Replace named variables ({key}, {key=default}) in text with provided or default values.
-    for (key, variable) in variables.named.iter_mut() {
-        // Create replace pattern like {key} or {key=default} and replace first occurrence.
-        let pattern = if variable.default.is_empty() {
-            format!("{{{}}}", variable.name)
-        } else {
-            format!("{{{0}={1}}}", variable.name, variable.default)
-        };
+        // Backspace or the undo key right after an expansion reverts it.
+        let undo = app_state
+            .undo_window
+            .lock()
+            .unwrap()
+            .handle(&event, &app_settings.undo);
+
+        if let Some(undo) = undo {
+            *app_state.snippet_session.lock().unwrap() = None;
+            capture.cancel();
+            tx.send(Signal::Undo(undo)).unwrap();
+            return;
+        }
 
-        match params_named.remove(key) {
-            Some(value) => {
-                text = text.replace(&pattern, value);
-            }
-            None => {
-                // Replace with default value if no value was provided.
-                text = text.replace(&pattern, &variable.default);
+        // Follow the typing inside the last expansion to know where its
+        // remaining tab stops are.
+        {
+            let mut snippet_session = app_state.snippet_session.lock().unwrap();
+
+            if let Some(session) = snippet_session.as_mut() {
+                match session.handle(&event, app_settings.snippets.next_stop_key) {
+                    SessionUpdate::Continue => (),
+                    SessionUpdate::End => *snippet_session = None,
+                    SessionUpdate::Move(caret_move) => {
+                        if session.is_finished() {
+                            *snippet_session = None;
+                        }
+
+                        tx.send(Signal::MoveCaret(caret_move)).unwrap();
+                        return;
+                    }
+                }
             }
         }
+
+        // Selection hotkeys apply an expansion without typing its abbreviation.
+        if let Some(hotkey) = app_settings.selection.hotkey(event.key) {
+            *app_state.snippet_session.lock().unwrap() = None;
+            capture.cancel();
+            tx.send(Signal::Selection(hotkey.clone())).unwrap();
+            return;
+        }
+
+        if let Some(signal) = capture.handle(&event, &app_settings) {
+            tx.send(Signal::Expand(signal)).unwrap();
+        }
+    })) {
+        println!("Error: {:?}", error)
     }
+}
 
-    let mut enigo: Enigo = Enigo::new(&Settings::default()).unwrap();
+fn expansion_context(
+    app: &tauri::AppHandle,
+    active_window: &Arc<Mutex<ActiveWindow>>,
+    app_settings: &AppSettings,
+) -> ExpansionContext {
+    let shell_permissions = app
+        .state::<AppState>()
+        .shell_permissions
+        .lock()
+        .unwrap()
+        .clone();
+
+    ExpansionContext::new(get_active_app(active_window))
+        .with_system(Arc::new(system::SystemReader))
+        .with_shell(shell_permissions, app_settings.shell.clone())
+}
 
-    // Set minimal delay if not on windows.
-    // #[cfg(not(target_os = "windows"))]
-    // {
-    //     enigo.set_delay(0);
-    // }
+/// Remember a typed expansion for undo, history, statistics and its tab stops.
+fn end_expansion(
+    app: &tauri::AppHandle,
+    signal: &CaptureSignal,
+    plan: ExpansionPlan,
+    entry: HistoryEntry,
+    active_window: &Arc<Mutex<ActiveWindow>>,
+    app_settings: &AppSettings,
+) {
+    let app_state = app.state::<AppState>();
 
-    let char_count_to_remove =
-        current_sequence.len() + append.len() + 1 + if append_enter { 1 } else { 0 };
+    let window_id = active_window.lock().unwrap().window_id.clone();
+    let last = LastExpansion::new(signal, &plan, &app_settings.trigger.string, &window_id);
 
-    // Undo captured sequence.
-    for _ in 0..char_count_to_remove {
-        let r = enigo.key(enigo::Key::Backspace, enigo::Direction::Click);
-        if r.is_err() {
-            println!("Error: {:?}", r);
-        }
+    // Undoing must never revert an older expansion instead of this one.
+    let mut undo_window = app_state.undo_window.lock().unwrap();
+    match last {
+        Some(last) => undo_window.open(last),
+        None => undo_window.close(),
     }
+    drop(undo_window);
+
+    let active_app = get_active_app(active_window);
+
+    record_history(app, &app_settings.history, entry);
+    record_stats(app, signal, &plan, &active_app);
 
-    // Wait for backspace to finish. This does not seem to be necessary on windows.
-    #[cfg(not(target_os = "windows"))]
-    {
-        let count = (std::cmp::max(char_count_to_remove * 10 / 2, 50))
-            .try_into()
-            .unwrap();
+    // Set while still injecting, so the session only sees the key presses of
+    // the user.
+    *app_state.snippet_session.lock().unwrap() = plan.snippet;
+}
+
+/// Ask for the variables of the capture in a small window if some of them
+/// were not typed. Returns whether the form was opened. The expansion is
+/// typed once the form is submitted.
+fn open_variable_form(
+    app: &tauri::AppHandle,
+    signal: &CaptureSignal,
+    active_window: &Arc<Mutex<ActiveWindow>>,
+    app_settings: &AppSettings,
+) -> bool {
+    let context = ExpansionContext::new(get_active_app(active_window));
 
-        std::thread::sleep(std::time::Duration::from_millis(count));
+    let Some(form) = typls_core::variable_form(signal, app_settings, &context) else {
+        return false;
+    };
+
+    let app_state = app.state::<AppState>();
+    *app_state.pending_form.lock().unwrap() = Some((signal.clone(), form));
+
+    // A form that is still open shows the new variables instead.
+    if let Some(window) = app.get_webview_window(FORM_WINDOW_LABEL) {
+        if let Err(error) = app.emit_to(FORM_WINDOW_LABEL, "variable-form", ()) {
+            println!("Error updating variable form: {:?}", error);
+        }
+
+        return window.set_focus().is_ok();
     }
 
-    let full_text = format!("{}{}", text, append);
-    enigo.text(full_text.as_str()).unwrap();
+    let window = WebviewWindowBuilder::new(app, FORM_WINDOW_LABEL, WebviewUrl::default())
+        .title("typls")
+        .inner_size(380.0, 440.0)
+        .resizable(false)
+        .always_on_top(true)
+        .center()
+        .focused(true)
+        .build();
+
+    match window {
+        Ok(window) => {
+            // Closing the window without submitting cancels the expansion.
+            let app_handle = app.clone();
+            window.on_window_event(move |event| {
+                if let WindowEvent::Destroyed = event {
+                    *app_handle.state::<AppState>().pending_form.lock().unwrap() = None;
+                }
+            });
 
-    if append_enter {
-        enigo
-            .key(enigo::Key::Return, enigo::Direction::Click)
-            .unwrap();
+            true
+        }
+        Err(error) => {
+            println!("Error opening variable form: {:?}", error);
+            *app_state.pending_form.lock().unwrap() = None;
+            false
+        }
     }
 }
 
-#[derive(Debug)]
-struct Variable {
-    name: String,
-    default: String,
+fn close_variable_form(app: &tauri::AppHandle) -> Result<(), String> {
+    match app.get_webview_window(FORM_WINDOW_LABEL) {
+        Some(window) => window.close().map_err(|error| error.to_string()),
+        None => Ok(()),
+    }
 }
 
-#[derive(Debug)]
-struct ExpansionVariables {
-    named: HashMap<String, Variable>,
-    unnamed: Vec<Variable>,
+/// Show problems with an expansion in the typls window instead of typing
+/// them into the focused app.
+fn report_errors(app: &tauri::AppHandle, errors: Vec<String>) {
+    for error in errors {
+        println!("Expansion error: {}", error);
+
+        if let Err(error) = app.emit("expansion-error", &error) {
+            println!("Error reporting expansion error: {:?}", error);
+        }
+    }
 }
 
-fn parse_variables(expansion: &Expansion) -> ExpansionVariables {
-    let mut named = HashMap::new();
-    let mut unnamed = Vec::new();
+fn get_active_app(active_window: &Arc<Mutex<ActiveWindow>>) -> ActiveApp {
+    let window_props = active_window.lock().unwrap();
 
-    let mut chars = expansion.text.chars();
+    ActiveApp {
+        process_path: window_props.process_path.to_string_lossy().to_string(),
+        platform: tauri_plugin_os::platform().to_string(),
+        name: window_props.app_name.clone(),
+        title: window_props.title.clone(),
+    }
+}
 
-    while let Some(c) = chars.next() {
-        if c == '{' {
-            let mut name = String::new();
-            let mut default = String::new();
+fn execute_plan(plan: &ExpansionPlan, sinks: &mut HashMap<OutputKind, Box<dyn OutputSink>>) {
+    let Some(sink) = get_output_sink(plan.output, sinks) else {
+        return;
+    };
 
-            while let Some(c) = chars.next() {
-                if c == '}' || c.is_whitespace() {
-                    break;
-                }
+    if let Err(error) = typls_core::execute(plan, sink) {
+        println!("Error: {}", error);
+    }
+}
 
-                if c == '=' {
-                    while let Some(c) = chars.next() {
-                        if c == '}' {
-                            break;
-                        }
+fn move_caret(caret_move: &CaretMove, sinks: &mut HashMap<OutputKind, Box<dyn OutputSink>>) {
+    let Some(sink) = get_output_sink(caret_move.output, sinks) else {
+        return;
+    };
 
-                        default.push(c);
-                    }
-                    break;
-                }
+    if let Err(error) = typls_core::execute_caret_move(caret_move, sink) {
+        println!("Error: {}", error);
+    }
+}
 
-                name.push(c);
-            }
+fn undo_expansion(undo: &UndoPlan, sinks: &mut HashMap<OutputKind, Box<dyn OutputSink>>) {
+    let Some(sink) = get_output_sink(undo.output, sinks) else {
+        return;
+    };
 
-            if name.is_empty() {
-                unnamed.push(Variable {
-                    name: "".to_string(),
-                    default,
-                });
-            } else {
-                named.insert(name.clone(), Variable { name, default });
-            }
-        }
+    if let Err(error) = typls_core::execute_undo(undo, sink) {
+        println!("Error: {}", error);
     }
+}
+
+fn get_output_sink(
+    kind: OutputKind,
+    sinks: &mut HashMap<OutputKind, Box<dyn OutputSink>>,
+) -> Option<&mut dyn OutputSink> {
+    // Sinks are created once and reused for all following expansions.
+    let sink = match sinks.entry(kind) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => match output::create_output_sink(kind) {
+            Ok(sink) => entry.insert(sink),
+            Err(error) => {
+                println!("Error creating output {:?}: {}", kind, error);
+                return None;
+            }
+        },
+    };
 
-    ExpansionVariables { named, unnamed }
+    Some(sink.as_mut())
 }
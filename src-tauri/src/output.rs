@@ -0,0 +1,254 @@
+use enigo::{Direction, Enigo, Keyboard, Settings};
+
+use typls_core::{OutputKind, OutputSink, PlanKey, RichText};
+
+use crate::clipboard;
+
+#[cfg(target_os = "linux")]
+use crate::keymap;
+
+/// Name of the virtual keyboard created by the uinput sink.
+#[cfg(target_os = "linux")]
+pub const UINPUT_DEVICE_NAME: &str = "typls virtual keyboard";
+
+/// Create the sink for the given output kind.
+pub fn create_output_sink(kind: OutputKind) -> Result<Box<dyn OutputSink>, String> {
+    match kind {
+        OutputKind::Enigo => Ok(Box::new(EnigoSink::new()?)),
+        OutputKind::Uinput => uinput_sink(),
+        OutputKind::Clipboard => Ok(Box::new(ClipboardSink::new()?)),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn uinput_sink() -> Result<Box<dyn OutputSink>, String> {
+    Ok(Box::new(UinputSink::new()?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn uinput_sink() -> Result<Box<dyn OutputSink>, String> {
+    println!("The uinput output is only available on Linux, using enigo");
+    Ok(Box::new(EnigoSink::new()?))
+}
+
+fn enigo_key(key: PlanKey) -> enigo::Key {
+    match key {
+        PlanKey::Return => enigo::Key::Return,
+        PlanKey::Left => enigo::Key::LeftArrow,
+        PlanKey::Right => enigo::Key::RightArrow,
+    }
+}
+
+/// Wait for erasing to finish. This does not seem to be necessary on windows.
+fn wait_for_erase(count: usize) {
+    #[cfg(not(target_os = "windows"))]
+    {
+        let millis = std::cmp::max(count * 10 / 2, 50).try_into().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(millis));
+    }
+
+    #[cfg(target_os = "windows")]
+    let _ = count;
+}
+
+/// Simulates key presses with enigo.
+pub struct EnigoSink {
+    enigo: Enigo,
+}
+
+impl EnigoSink {
+    pub fn new() -> Result<EnigoSink, String> {
+        let enigo = Enigo::new(&Settings::default()).map_err(|error| format!("{:?}", error))?;
+
+        // Set minimal delay if not on windows.
+        // #[cfg(not(target_os = "windows"))]
+        // {
+        //     enigo.set_delay(0);
+        // }
+
+        Ok(EnigoSink { enigo })
+    }
+}
+
+impl OutputSink for EnigoSink {
+    fn erase(&mut self, count: usize) -> Result<(), String> {
+        for _ in 0..count {
+            let r = self.enigo.key(enigo::Key::Backspace, Direction::Click);
+            if r.is_err() {
+                println!("Error: {:?}", r);
+            }
+        }
+
+        wait_for_erase(count);
+
+        Ok(())
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<(), String> {
+        self.enigo
+            .text(text)
+            .map_err(|error| format!("{:?}", error))
+    }
+
+    fn press(&mut self, key: PlanKey) -> Result<(), String> {
+        self.enigo
+            .key(enigo_key(key), Direction::Click)
+            .map_err(|error| format!("{:?}", error))
+    }
+}
+
+/// Puts the text on the clipboard and pastes it with the platform shortcut,
+/// then restores the previous clipboard contents. Erasing and other keys are
+/// still simulated with enigo.
+pub struct ClipboardSink {
+    keys: EnigoSink,
+    clipboard: arboard::Clipboard,
+}
+
+impl ClipboardSink {
+    pub fn new() -> Result<ClipboardSink, String> {
+        Ok(ClipboardSink {
+            keys: EnigoSink::new()?,
+            clipboard: arboard::Clipboard::new().map_err(|error| error.to_string())?,
+        })
+    }
+}
+
+impl ClipboardSink {
+    /// Put something on the clipboard, paste it and restore the previous
+    /// contents afterwards.
+    fn paste(
+        &mut self,
+        set: impl FnOnce(&mut arboard::Clipboard) -> Result<(), arboard::Error>,
+    ) -> Result<(), String> {
+        let saved = clipboard::save(&mut self.clipboard);
+
+        set(&mut self.clipboard).map_err(|error| error.to_string())?;
+
+        let result = clipboard::press_shortcut(&mut self.keys.enigo, 'v');
+
+        // Give the focused application time to read the clipboard before
+        // restoring it.
+        std::thread::sleep(std::time::Duration::from_millis(CLIPBOARD_RESTORE_DELAY));
+
+        clipboard::restore(&mut self.clipboard, saved)?;
+
+        result
+    }
+}
+
+impl OutputSink for ClipboardSink {
+    fn erase(&mut self, count: usize) -> Result<(), String> {
+        self.keys.erase(count)
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<(), String> {
+        self.paste(|clipboard| clipboard.set_text(text))
+    }
+
+    fn press(&mut self, key: PlanKey) -> Result<(), String> {
+        self.keys.press(key)
+    }
+
+    /// Offers both HTML and plain text, so the focused app picks the richest
+    /// format it supports.
+    fn type_rich_text(&mut self, rich: &RichText) -> Result<(), String> {
+        self.paste(|clipboard| clipboard.set_html(&rich.html, Some(&rich.plain)))
+    }
+}
+
+/// Milliseconds to wait after pasting before the clipboard is restored.
+const CLIPBOARD_RESTORE_DELAY: u64 = 150;
+
+/// Types with a virtual keyboard created through `/dev/uinput`. This works
+/// on Wayland, where enigo can not simulate key presses. The user needs
+/// write access to `/dev/uinput`.
+///
+/// Only characters of a US keyboard layout can be typed.
+#[cfg(target_os = "linux")]
+pub struct UinputSink {
+    device: evdev::uinput::VirtualDevice,
+}
+
+#[cfg(target_os = "linux")]
+impl UinputSink {
+    pub fn new() -> Result<UinputSink, String> {
+        let mut keys = evdev::AttributeSet::<evdev::Key>::new();
+        for key in keymap::layout_keys() {
+            keys.insert(key);
+        }
+        keys.insert(evdev::Key::KEY_BACKSPACE);
+        keys.insert(evdev::Key::KEY_LEFTSHIFT);
+        keys.insert(evdev::Key::KEY_LEFT);
+        keys.insert(evdev::Key::KEY_RIGHT);
+
+        let device = evdev::uinput::VirtualDeviceBuilder::new()
+            .and_then(|builder| builder.name(UINPUT_DEVICE_NAME).with_keys(&keys))
+            .and_then(|builder| builder.build())
+            .map_err(|error| format!("Could not create uinput keyboard: {}", error))?;
+
+        // The compositor needs a moment to pick up the new device.
+        std::thread::sleep(std::time::Duration::from_millis(200));
+
+        Ok(UinputSink { device })
+    }
+
+    fn click(&mut self, key: evdev::Key, shift: bool) -> Result<(), String> {
+        let event = |key: evdev::Key, value| {
+            evdev::InputEvent::new(evdev::EventType::KEY, key.code(), value)
+        };
+
+        let mut events = vec![];
+        if shift {
+            events.push(event(evdev::Key::KEY_LEFTSHIFT, 1));
+        }
+        events.push(event(key, 1));
+        events.push(event(key, 0));
+        if shift {
+            events.push(event(evdev::Key::KEY_LEFTSHIFT, 0));
+        }
+
+        self.device
+            .emit(&events)
+            .map_err(|error| error.to_string())?;
+
+        // Applications drop key presses that arrive too quickly.
+        std::thread::sleep(std::time::Duration::from_millis(2));
+
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl OutputSink for UinputSink {
+    fn erase(&mut self, count: usize) -> Result<(), String> {
+        for _ in 0..count {
+            self.click(evdev::Key::KEY_BACKSPACE, false)?;
+        }
+
+        wait_for_erase(count);
+
+        Ok(())
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<(), String> {
+        for c in text.chars() {
+            match keymap::text_key(c.encode_utf8(&mut [0; 4])) {
+                Some((key, shift)) => self.click(key, shift)?,
+                None => println!("Can not type {:?} with the uinput keyboard", c),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn press(&mut self, key: PlanKey) -> Result<(), String> {
+        let key = match key {
+            PlanKey::Return => evdev::Key::KEY_ENTER,
+            PlanKey::Left => evdev::Key::KEY_LEFT,
+            PlanKey::Right => evdev::Key::KEY_RIGHT,
+        };
+
+        self.click(key, false)
+    }
+}
@@ -0,0 +1,35 @@
+use typls_core::SystemSource;
+
+use crate::clipboard;
+
+/// Reads the values of built-in variables from the system. The selection is
+/// copied with the copy shortcut, so it must only be read while key presses
+/// are not captured. After typing an abbreviation usually nothing is
+/// selected, but some editors copy the whole line then.
+pub struct SystemReader;
+
+impl SystemSource for SystemReader {
+    fn clipboard(&self) -> Option<String> {
+        clipboard::read_text()
+    }
+
+    fn selection(&self) -> Option<String> {
+        match clipboard::copy_selection() {
+            Ok(selection) => selection,
+            Err(error) => {
+                println!("Error reading selection: {}", error);
+                None
+            }
+        }
+    }
+
+    fn user_name(&self) -> Option<String> {
+        std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .ok()
+    }
+
+    fn host_name(&self) -> Option<String> {
+        Some(tauri_plugin_os::hostname()).filter(|name| !name.is_empty())
+    }
+}
@@ -3,17 +3,24 @@ export type Expansion = {
   abbr: string;
   text: string;
   group?: string | null;
+  output?: OutputKind | null;
+  format?: ExpansionFormat;
 };
 
+export type ExpansionFormat = "plain" | "markdown" | "html";
+
 export type App = {
   path: string;
   os: string;
 };
 
+export type OutputKind = "enigo" | "uinput" | "clipboard";
+
 export type Group = {
   id: string;
   name: string;
   apps: App[];
+  output?: OutputKind | null;
 };
 
 export type Settings = {
@@ -29,8 +36,110 @@ export type Settings = {
   };
   variables: {
     separator: string;
+    form?: boolean;
   };
   expansions: Expansion[];
   groups: Group[];
   activeGroup: Group["id"];
+  input?: {
+    backend: "auto" | "rdev" | "evdev" | "replay";
+    replayFile?: string | null;
+  };
+  output?: {
+    kind: OutputKind;
+    pasteThreshold?: number | null;
+  };
+  snippets?: {
+    nextStopKey: Key;
+  };
+  undo?: {
+    backspace: boolean;
+    key?: Key | null;
+  };
+  history?: {
+    mode: "off" | "memory" | "file";
+    limit: number;
+  };
+  suggestions?: {
+    enabled: boolean;
+    minCount: number;
+  };
+  selection?: {
+    hotkeys: SelectionHotkey[];
+  };
+  shell?: {
+    timeout: number;
+    maxOutput: number;
+  };
+};
+
+export type HistoryEntry = {
+  id: string;
+  expansionId: string;
+  abbr: string;
+  params: {
+    unnamed: string[];
+    named: Record<string, string>;
+  };
+  text: string;
+  rich?: { html: string; plain: string } | null;
+  output: OutputKind;
+  app: string;
+  timestamp: number;
+};
+
+export type SelectionHotkey = {
+  key: Key;
+  sequence: string;
+};
+
+export type FormField = {
+  name: string;
+  default: string;
+  value?: string | null;
+  choices: string[];
+};
+
+export type VariableForm = {
+  expansionId: string;
+  abbr: string;
+  fields: FormField[];
+};
+
+export type ShellPermissions = {
+  allowed: string[];
+  expansions: Record<string, string[]>;
+};
+
+export type Key =
+  | "return"
+  | "leftArrow"
+  | "rightArrow"
+  | "escape"
+  | "backspace"
+  | "tab"
+  | { function: number }
+  | "modifier"
+  | "other";
+
+export type ExpansionStats = {
+  count: number;
+  lastUsed: number;
+  charsTyped: number;
+  charsProduced: number;
+  apps: Record<string, number>;
+};
+
+export type StatsSummary = {
+  count: number;
+  charsTyped: number;
+  charsProduced: number;
+  charsSaved: number;
+  apps: Record<string, number>;
+  expansions: (ExpansionStats & { expansionId: string; abbr: string })[];
+};
+
+export type PhraseSuggestion = {
+  expansion: Expansion;
+  count: number;
 };
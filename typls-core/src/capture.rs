@@ -0,0 +1,163 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::expand::{find_unambiguous_expansion, is_param_quoted, Params};
+use crate::input::{Key, KeyEvent};
+use crate::plan::ExpansionPlan;
+use crate::session::CaretMove;
+use crate::settings::{AppSettings, SelectionHotkey};
+use crate::undo::UndoPlan;
+use crate::variables::has_variables;
+
+/// A captured sequence that was confirmed and should be expanded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureSignal {
+    /// The typed abbreviation and parameters, without the trigger.
+    pub sequence: String,
+    /// The confirm character that should be typed after the expansion.
+    pub append: String,
+    /// Whether the capture was confirmed with the enter key.
+    pub append_enter: bool,
+}
+
+/// Work for the executor, sent by the keyboard listener.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Signal {
+    Expand(CaptureSignal),
+    /// Jump to the next tab stop of the last expansion.
+    MoveCaret(CaretMove),
+    /// Revert the last expansion.
+    Undo(UndoPlan),
+    /// Type the text of a previous expansion again.
+    Insert(ExpansionPlan),
+    /// Apply the expansion of a selection hotkey to the selected text.
+    Selection(SelectionHotkey),
+    /// Expand a capture with the values entered in its variable form.
+    Form(CaptureSignal, Params),
+    /// Write the phrase counts to disk, which is too slow for the listener.
+    SavePhrases,
+}
+
+/// Tracks the typed keys and decides when a sequence starts and when it is
+/// confirmed, cancelled or continued.
+#[derive(Debug, Default)]
+pub struct Capture {
+    current_sequence: String,
+    is_capturing: bool,
+}
+
+impl Capture {
+    pub fn new() -> Capture {
+        Capture::default()
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.is_capturing
+    }
+
+    /// The sequence typed since the trigger.
+    pub fn sequence(&self) -> &str {
+        &self.current_sequence
+    }
+
+    /// Feed a key press into the state machine. Returns a signal when a
+    /// captured sequence was confirmed.
+    pub fn handle(&mut self, event: &KeyEvent, settings: &AppSettings) -> Option<CaptureSignal> {
+        if settings.trigger.string.is_empty() {
+            return None;
+        }
+
+        match event.key {
+            // Confirm capture without appending anything.
+            Key::RightArrow | Key::Return => {
+                if !self.is_capturing {
+                    return None;
+                }
+
+                // Return if confirm via the pressed key is disabled.
+                if (!settings.confirm.key_right_arrow && event.key == Key::RightArrow)
+                    || (!settings.confirm.key_enter && event.key == Key::Return)
+                {
+                    return None;
+                }
+
+                return Some(self.confirm("", event.key == Key::Return));
+            }
+            // Cancel capture.
+            Key::Escape => {
+                self.cancel();
+                return None;
+            }
+            Key::Backspace => {
+                if !self.is_capturing {
+                    return None;
+                }
+
+                // Remove the last grapheme, just like the focused app does.
+                match self.current_sequence.grapheme_indices(true).next_back() {
+                    Some((index, _)) => self.current_sequence.truncate(index),
+                    None => self.is_capturing = false,
+                }
+
+                return None;
+            }
+            Key::LeftArrow | Key::Tab | Key::Function(_) | Key::Modifier | Key::Other => (),
+        }
+
+        let string = event.name.as_deref()?;
+
+        if string.is_empty() {
+            return None;
+        }
+
+        if !self.is_capturing {
+            if string == settings.trigger.string {
+                self.current_sequence = String::new();
+                self.is_capturing = true;
+            }
+
+            return None;
+        }
+
+        // TODO: Make tab work? string == '\t'
+        // Quoted parameters may contain confirm characters, like
+        // `'link|"https://example.com"`.
+        if settings.confirm.chars.iter().any(|c| c == string)
+            && !is_param_quoted(&self.current_sequence, settings)
+        {
+            let append = if settings.confirm.append { string } else { "" };
+            return Some(self.confirm(append, false));
+        }
+
+        self.current_sequence.push_str(string);
+
+        if settings.confirm.auto {
+            if let Some(matching_expansion) =
+                find_unambiguous_expansion(&self.current_sequence, settings)
+            {
+                if !has_variables(&matching_expansion.text) {
+                    return Some(self.confirm("", false));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn confirm(&mut self, append: &str, append_enter: bool) -> CaptureSignal {
+        let signal = CaptureSignal {
+            sequence: self.current_sequence.clone(),
+            append: append.to_string(),
+            append_enter,
+        };
+
+        self.cancel();
+
+        signal
+    }
+
+    /// Stop capturing and forget the typed sequence.
+    pub fn cancel(&mut self) {
+        self.is_capturing = false;
+        self.current_sequence = String::new();
+    }
+}
@@ -0,0 +1,347 @@
+use std::cell::{OnceCell, RefCell};
+use std::sync::Arc;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Duration, Local, Months, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::expand::ActiveApp;
+use crate::settings::ShellSettings;
+use crate::shell::{run_command, ShellPermissions};
+
+/// Variables typls fills in itself, like `{date}`.
+pub const BUILTIN_VARIABLES: [&str; 8] = [
+    "date",
+    "time",
+    "clipboard",
+    "selection",
+    "shell",
+    "env",
+    "system",
+    "app",
+];
+
+/// Clipboard contents longer than this (in bytes) are not inserted, typing
+/// them would take ages.
+pub const MAX_CLIPBOARD_LENGTH: usize = 64 * 1024;
+
+/// Reads values from the system. They are only read if an expansion uses
+/// them.
+pub trait SystemSource {
+    /// The text on the clipboard, without changing it. `None` if the
+    /// clipboard is empty or does not contain text.
+    fn clipboard(&self) -> Option<String>;
+
+    /// The selected text in the focused application. `None` if nothing is
+    /// selected or the selection can not be read.
+    fn selection(&self) -> Option<String> {
+        None
+    }
+
+    /// Name of the logged in user.
+    fn user_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Name of this machine on the network.
+    fn host_name(&self) -> Option<String> {
+        None
+    }
+}
+
+/// The situation an expansion is typed in. Built-in variables are evaluated
+/// with it.
+#[derive(Clone)]
+pub struct ExpansionContext {
+    pub active_app: ActiveApp,
+    pub now: DateTime<Utc>,
+    pub system: Option<Arc<dyn SystemSource>>,
+    clipboard: OnceCell<Option<String>>,
+    selection: OnceCell<Option<String>>,
+    shell: Option<(ShellPermissions, ShellSettings)>,
+    errors: RefCell<Vec<String>>,
+}
+
+impl Default for ExpansionContext {
+    fn default() -> Self {
+        ExpansionContext {
+            active_app: ActiveApp::default(),
+            now: Utc::now(),
+            system: None,
+            clipboard: OnceCell::new(),
+            selection: OnceCell::new(),
+            shell: None,
+            errors: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl std::fmt::Debug for ExpansionContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExpansionContext")
+            .field("active_app", &self.active_app)
+            .field("now", &self.now)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ExpansionContext {
+    pub fn new(active_app: ActiveApp) -> ExpansionContext {
+        ExpansionContext {
+            active_app,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_now(mut self, now: DateTime<Utc>) -> ExpansionContext {
+        self.now = now;
+        self
+    }
+
+    pub fn with_system(mut self, system: Arc<dyn SystemSource>) -> ExpansionContext {
+        self.system = Some(system);
+        self
+    }
+
+    /// Allow `{shell:...}` variables to run the permitted commands.
+    pub fn with_shell(
+        mut self,
+        permissions: ShellPermissions,
+        settings: ShellSettings,
+    ) -> ExpansionContext {
+        self.shell = Some((permissions, settings));
+        self
+    }
+
+    /// Record a problem that should be shown to the user.
+    pub(crate) fn report_error(&self, error: String) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    /// Take the problems that occurred while evaluating built-in variables.
+    /// They are meant to be shown to the user, never typed.
+    pub fn take_errors(&self) -> Vec<String> {
+        self.errors.take()
+    }
+
+    /// Value of a built-in variable of the expansion, like `date:+7d:%A` or
+    /// `env:HOME`. Returns `None` if the name is no built-in variable, its
+    /// arguments are invalid or the value is not available.
+    pub fn builtin(&self, name: &str, expansion_id: &str) -> Option<String> {
+        let (base, args) = name.split_once(':').unwrap_or((name, ""));
+
+        let value = match base {
+            "date" => self.date_time(args, "%Y-%m-%d")?,
+            "time" => self.date_time(args, "%H:%M")?,
+            "clipboard" => self.clipboard()?,
+            "selection" => self.selection()?,
+            "shell" => self.shell(args, expansion_id)?,
+            "env" => std::env::var(args).ok()?,
+            "system" => self.system_info(args)?,
+            "app" => self.app_info(args)?,
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    /// The clipboard text, read only once per expansion.
+    fn clipboard(&self) -> Option<String> {
+        self.clipboard
+            .get_or_init(|| {
+                self.system
+                    .as_ref()?
+                    .clipboard()
+                    .filter(|text| text.len() <= MAX_CLIPBOARD_LENGTH)
+            })
+            .clone()
+    }
+
+    /// Information about the machine, like `system:host`.
+    fn system_info(&self, field: &str) -> Option<String> {
+        match field {
+            "user" => self.system.as_ref()?.user_name(),
+            "host" => self.system.as_ref()?.host_name(),
+            "os" => Some(self.active_app.platform.clone()),
+            _ => None,
+        }
+    }
+
+    /// Information about the focused application, like `app:title`. Just
+    /// `app` is its name.
+    fn app_info(&self, field: &str) -> Option<String> {
+        let value = match field {
+            "" | "name" => &self.active_app.name,
+            "path" => &self.active_app.process_path,
+            "title" => &self.active_app.title,
+            _ => return None,
+        };
+
+        Some(value.clone()).filter(|value| !value.is_empty())
+    }
+
+    /// The current date or time. Invalid arguments are recorded as errors.
+    fn date_time(&self, args: &str, default_format: &str) -> Option<String> {
+        format_date_time(self.now, args, default_format)
+            .map_err(|error| self.report_error(error))
+            .ok()
+    }
+
+    /// Output of a permitted command. Failures are recorded as errors.
+    fn shell(&self, command: &str, expansion_id: &str) -> Option<String> {
+        let result = match &self.shell {
+            Some((permissions, settings)) if permissions.is_allowed(command, expansion_id) => {
+                run_command(command, settings)
+            }
+            _ => Err(format!("`{}` is not allowed to run", command)),
+        };
+
+        result.map_err(|error| self.report_error(error)).ok()
+    }
+
+    /// The selected text, read only once per expansion.
+    fn selection(&self) -> Option<String> {
+        self.selection
+            .get_or_init(|| {
+                self.system
+                    .as_ref()?
+                    .selection()
+                    .filter(|text| text.len() <= MAX_CLIPBOARD_LENGTH)
+            })
+            .clone()
+    }
+}
+
+/// The name of a variable without arguments and filters, like `date` for
+/// `date:%A`.
+pub fn variable_base(name: &str) -> &str {
+    name.split([':', '|']).next().unwrap_or_default()
+}
+
+/// Whether the variable name refers to a built-in variable.
+pub fn is_builtin(name: &str) -> bool {
+    BUILTIN_VARIABLES.contains(&variable_base(name))
+}
+
+/// A shift of the current time, like `+7d`.
+enum Offset {
+    Duration(Duration),
+    Months(i32),
+}
+
+/// Format the time with optional arguments: any number of offsets (`+7d`,
+/// `-2h`, `+1M`) and a time zone (`UTC`, `Europe/Berlin`), separated by
+/// colons and followed by a strftime format. The local time zone is used by
+/// default. Fails for arguments that are none of these and for invalid
+/// formats.
+fn format_date_time(
+    now: DateTime<Utc>,
+    args: &str,
+    default_format: &str,
+) -> Result<String, String> {
+    let mut offsets = vec![];
+    let mut zone = None;
+    let mut rest = args;
+
+    while !rest.is_empty() {
+        let (segment, remainder) = rest.split_once(':').unwrap_or((rest, ""));
+
+        if let Some(offset) = parse_offset(segment) {
+            offsets.push(offset);
+        } else if let Ok(tz) = segment.parse::<Tz>() {
+            zone = Some(tz);
+        } else {
+            break;
+        }
+
+        rest = remainder;
+    }
+
+    let format = if rest.is_empty() {
+        default_format
+    } else {
+        rest
+    };
+
+    // Formats start with something that looks like an offset or a time
+    // zone, like `+7x` or `Europe/Berln`, or have no specifier at all if
+    // one of them was mistyped.
+    let segment = format.split(':').next().unwrap_or_default();
+    let is_mistyped = segment.starts_with(['+', '-'])
+        || (segment.contains('/') && !segment.contains('%'))
+        || !format.contains('%');
+
+    if is_mistyped {
+        return Err(format!(
+            "`{}` is no offset, time zone or date format",
+            segment
+        ));
+    }
+
+    // Invalid formats would panic while formatting.
+    let items = StrftimeItems::new(format).collect::<Vec<_>>();
+    if items.contains(&Item::Error) {
+        return Err(format!("Invalid date format `{}`", format));
+    }
+
+    let time = match zone {
+        Some(zone) => format_shifted(now.with_timezone(&zone), &offsets, &items),
+        None => format_shifted(now.with_timezone(&Local), &offsets, &items),
+    };
+
+    time.ok_or_else(|| format!("`{}` is out of the supported date range", args))
+}
+
+fn format_shifted<Z: TimeZone>(
+    mut time: DateTime<Z>,
+    offsets: &[Offset],
+    items: &[Item],
+) -> Option<String>
+where
+    Z::Offset: std::fmt::Display,
+{
+    for offset in offsets {
+        time = match *offset {
+            Offset::Duration(duration) => time.checked_add_signed(duration)?,
+            Offset::Months(months) if months < 0 => {
+                time.checked_sub_months(Months::new(months.unsigned_abs()))?
+            }
+            Offset::Months(months) => time.checked_add_months(Months::new(months as u32))?,
+        };
+    }
+
+    Some(time.format_with_items(items.iter()).to_string())
+}
+
+/// Parse an offset like `+7d`. Units are `s`, `m`, `h`, `d`, `w`, `M`
+/// (months) and `y`.
+fn parse_offset(segment: &str) -> Option<Offset> {
+    if segment.len() < 3 {
+        return None;
+    }
+
+    let sign = match segment.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+
+    let unit = segment.chars().last()?;
+    let amount = segment[1..segment.len() - unit.len_utf8()]
+        .parse::<i32>()
+        .ok()?
+        * sign;
+
+    let offset = match unit {
+        's' => Offset::Duration(Duration::try_seconds(amount.into())?),
+        'm' => Offset::Duration(Duration::try_minutes(amount.into())?),
+        'h' => Offset::Duration(Duration::try_hours(amount.into())?),
+        'd' => Offset::Duration(Duration::try_days(amount.into())?),
+        'w' => Offset::Duration(Duration::try_weeks(amount.into())?),
+        'M' => Offset::Months(amount),
+        'y' => Offset::Months(amount.checked_mul(12)?),
+        _ => return None,
+    };
+
+    Some(offset)
+}
@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::context::{variable_base, ExpansionContext};
+use crate::format::{escape_html, insert_markdown_values, ExpansionFormat};
+use crate::settings::{AppSettings, Expansion};
+use crate::variables::{parse_template, variables_of, Node, Variable};
+
+/// The application that currently has focus. Used to pick expansions by the
+/// apps assigned to their group.
+#[derive(Debug, Default, Clone)]
+pub struct ActiveApp {
+    pub process_path: String,
+    pub platform: String,
+    pub name: String,
+    /// Title of the focused window.
+    pub title: String,
+}
+
+/// Parameters passed after the abbreviation, like `'hi|Peter|kind=meet`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Params {
+    pub unnamed: Vec<String>,
+    pub named: HashMap<String, String>,
+}
+
+impl Params {
+    /// Extract parameters into named and unnamed. Named parameters are split
+    /// at the first `=`. A value in double quotes is taken as it is, like
+    /// `"a=b"` or `url="https://x?a=b"`. Quotes anywhere else are part of
+    /// the value.
+    pub fn parse<'a>(parts: impl Iterator<Item = &'a str>) -> Params {
+        let mut params = Params::default();
+
+        for part in parts {
+            let (key, value) = match part.split_once('=') {
+                Some((key, value)) if !part.starts_with('"') => (key, unquote(value)),
+                _ => ("", unquote(part)),
+            };
+
+            if key.is_empty() {
+                params.unnamed.push(value);
+            } else {
+                params.named.insert(key.to_string(), value);
+            }
+        }
+
+        params
+    }
+}
+
+/// Split parameters at the separator, except inside of quoted values. Also
+/// returns whether the last value is an open quote.
+fn split_params<'a>(params: &'a str, separator: &str) -> (Vec<&'a str>, bool) {
+    let mut parts = vec![];
+    let mut start = 0;
+    // Quotes only start a value, either right after the separator or after
+    // the `=` of a named parameter.
+    let mut value_start = 0;
+    let mut has_name = false;
+    let mut index = 0;
+
+    while let Some(c) = params[index..].chars().next() {
+        if c == '"' && index == value_start {
+            match closing_quote(&params[index..]) {
+                Some(end) => {
+                    index += end + 1;
+                    continue;
+                }
+                // An open quote takes the rest of the parameters.
+                None => {
+                    parts.push(&params[start..]);
+                    return (parts, true);
+                }
+            }
+        }
+
+        if !separator.is_empty() && params[index..].starts_with(separator) {
+            parts.push(&params[start..index]);
+            index += separator.len();
+            start = index;
+            value_start = index;
+            has_name = false;
+            continue;
+        }
+
+        if c == '=' && !has_name {
+            has_name = true;
+            value_start = index + 1;
+        }
+
+        index += c.len_utf8();
+    }
+
+    parts.push(&params[start..]);
+
+    (parts, false)
+}
+
+/// The position of the quote closing the one the text starts with. In
+/// quotes, `\"` and `\\` stand for a quote and a backslash.
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1);
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '"' => return Some(index),
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Remove the quotes around a value and resolve the escapes in them. Values
+/// that do not start with a quote are returned as they are.
+fn unquote(value: &str) -> String {
+    if !value.starts_with('"') {
+        return value.to_string();
+    }
+
+    let end = closing_quote(value).unwrap_or(value.len());
+    let mut unquoted = String::with_capacity(value.len());
+    let mut chars = value[1..end].chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c @ ('"' | '\\')) => unquoted.push(c),
+                Some(c) => {
+                    unquoted.push('\\');
+                    unquoted.push(c);
+                }
+                None => unquoted.push('\\'),
+            },
+            c => unquoted.push(c),
+        }
+    }
+
+    // Anything after the closing quote is kept.
+    unquoted.push_str(value.get(end + 1..).unwrap_or_default());
+
+    unquoted
+}
+
+/// Expand a captured sequence (abbreviation and parameters, without the
+/// trigger) into the final text. Returns `None` if no expansion matches.
+pub fn expand(
+    sequence: &str,
+    settings: &AppSettings,
+    context: &ExpansionContext,
+) -> Option<String> {
+    resolve(sequence, settings, context).map(|(_, rendered)| rendered.into_text())
+}
+
+/// Find the expansion for a captured sequence and render its text.
+pub(crate) fn resolve<'a>(
+    sequence: &str,
+    settings: &'a AppSettings,
+    context: &ExpansionContext,
+) -> Option<(&'a Expansion, Rendered)> {
+    let (abbr, params) = split_sequence(sequence, settings);
+
+    resolve_with(abbr, params, settings, context)
+}
+
+/// Find the expansion for an abbreviation and render its text with the
+/// given parameters.
+pub(crate) fn resolve_with<'a>(
+    abbr: &str,
+    params: Params,
+    settings: &'a AppSettings,
+    context: &ExpansionContext,
+) -> Option<(&'a Expansion, Rendered)> {
+    let expansion = find_expansion(abbr, settings, &context.active_app)?;
+
+    match render_markers(expansion, params, context) {
+        Ok(rendered) => Some((expansion, rendered)),
+        Err(error) => {
+            context.report_error(error);
+            None
+        }
+    }
+}
+
+/// Split a captured sequence into the abbreviation and its parameters.
+/// Separators in double quotes are part of the parameter.
+pub fn split_sequence<'a>(sequence: &'a str, settings: &AppSettings) -> (&'a str, Params) {
+    let separator = settings.variables.separator.as_str();
+
+    // Extract abbreviation (first element).
+    let Some((abbr, rest)) = sequence.split_once(separator) else {
+        return (sequence, Params::default());
+    };
+
+    let (parts, _) = split_params(rest, separator);
+
+    (abbr, Params::parse(parts.into_iter()))
+}
+
+/// Whether the parameters of a sequence that is still typed end in an open
+/// quote, so confirm characters belong to the value.
+pub(crate) fn is_param_quoted(sequence: &str, settings: &AppSettings) -> bool {
+    sequence
+        .split_once(settings.variables.separator.as_str())
+        .is_some_and(|(_, params)| split_params(params, &settings.variables.separator).1)
+}
+
+/// Find the expansion for the abbreviation, preferring the ones in the active
+/// group or the group matching the active app.
+pub fn find_expansion<'a>(
+    abbr: &str,
+    settings: &'a AppSettings,
+    active_app: &ActiveApp,
+) -> Option<&'a Expansion> {
+    // Find all matching expansions.
+    let mut matching_expansions = settings
+        .expansions
+        .iter()
+        .filter(|&e| e.abbr == abbr)
+        .collect::<Vec<_>>();
+
+    if matching_expansions.is_empty() {
+        return None;
+    }
+
+    // Sort expansions so the ones assigned to a group are placed first.
+    // This is done so we always check constrained expansions first.
+    matching_expansions.sort_by_key(|e| e.group.is_none());
+
+    let mut chosen_expansion = None;
+
+    // Select first expansion when active group is set
+    if let Some(active_group) = &settings.active_group {
+        let active_group_expansions = matching_expansions
+            .iter()
+            .filter(|&e| e.group.is_none() || e.group.as_ref() == Some(active_group))
+            .collect::<Vec<_>>();
+
+        if !active_group_expansions.is_empty() {
+            chosen_expansion = Some(*active_group_expansions[0]);
+        }
+    }
+
+    if chosen_expansion.is_none() {
+        // Find expansion by group matching the active window/app.
+        for exp in matching_expansions.iter() {
+            if let Some(group_id) = &exp.group {
+                // find group in settings that has matching id
+                let group = settings
+                    .groups
+                    .as_ref()
+                    .and_then(|groups| groups.iter().find(|&g| g.id == *group_id));
+
+                let Some(group) = group else {
+                    continue;
+                };
+
+                if group
+                    .apps
+                    .iter()
+                    .any(|app| app.os == active_app.platform && app.path == active_app.process_path)
+                {
+                    chosen_expansion = Some(*exp);
+                    break;
+                }
+            } else {
+                chosen_expansion = Some(*exp);
+            }
+        }
+    }
+
+    chosen_expansion
+}
+
+/// The expansion with exactly the given abbreviation, as long as no other
+/// expansion starts with it. Used to auto confirm a capture.
+pub fn find_unambiguous_expansion<'a>(
+    sequence: &str,
+    settings: &'a AppSettings,
+) -> Option<&'a Expansion> {
+    let matching_expansion = settings.expansions.iter().find(|&e| e.abbr == sequence);
+
+    let expansions_starting_with_sequence = settings
+        .expansions
+        .iter()
+        .filter(|&e| e.abbr.starts_with(sequence))
+        .count();
+
+    if expansions_starting_with_sequence == 1 {
+        matching_expansion
+    } else {
+        None
+    }
+}
+
+/// A rendered expansion text. Cursor markers and tab stops are replaced by
+/// placeholders, so values that look like markers stay text.
+#[derive(Debug)]
+pub(crate) struct Rendered {
+    pub text: String,
+    /// The markers in the order of their placeholders, like `{|}` or `{$1}`.
+    pub markers: Vec<String>,
+}
+
+impl Rendered {
+    /// The text with the markers written back where they were.
+    pub fn into_text(self) -> String {
+        self.text
+            .chars()
+            .map(|c| match marker_index(c) {
+                Some(index) => self.markers[index].clone(),
+                None => c.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// The first code point of the Supplementary Private Use Area-B, which no
+/// font or app uses in practice. The placeholder of the marker at `index`
+/// is this plus the index.
+const MARKER_PLACEHOLDER_START: u32 = 0x100000;
+
+fn marker_placeholder(index: usize) -> char {
+    char::from_u32(MARKER_PLACEHOLDER_START + index as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// The index of the marker a placeholder stands for.
+pub(crate) fn marker_index(c: char) -> Option<usize> {
+    (c as u32)
+        .checked_sub(MARKER_PLACEHOLDER_START)
+        .map(|index| index as usize)
+}
+
+/// The first code point of the Supplementary Private Use Area-A. Values in
+/// Markdown texts are replaced by this plus their index until they can be
+/// escaped.
+const VALUE_PLACEHOLDER_START: u32 = 0xF0000;
+
+fn value_placeholder(index: usize) -> char {
+    char::from_u32(VALUE_PLACEHOLDER_START + index as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+fn value_index(c: char) -> Option<usize> {
+    (VALUE_PLACEHOLDER_START..MARKER_PLACEHOLDER_START)
+        .contains(&(c as u32))
+        .then(|| (c as u32 - VALUE_PLACEHOLDER_START) as usize)
+}
+
+/// Whether the character could be taken for a placeholder of a marker or
+/// a value.
+fn is_placeholder(c: char) -> bool {
+    c as u32 >= VALUE_PLACEHOLDER_START
+}
+
+/// Append text to the rendered text, without anything that could be taken
+/// for a placeholder.
+fn push_text(text: &mut String, value: &str) {
+    text.extend(value.chars().filter(|&c| !is_placeholder(c)));
+}
+
+/// Replace the variables in the expansion text with the given parameters,
+/// the values of built-in variables or their default values, apply their
+/// filters and keep the conditional sections whose condition is met. Fails
+/// if the text contains invalid variables or sections.
+pub fn render(
+    expansion: &Expansion,
+    params: Params,
+    context: &ExpansionContext,
+) -> Result<String, String> {
+    render_markers(expansion, params, context).map(Rendered::into_text)
+}
+
+/// Render the text like [`render`], but keep track of the markers instead
+/// of writing them into the text.
+pub(crate) fn render_markers(
+    expansion: &Expansion,
+    params: Params,
+    context: &ExpansionContext,
+) -> Result<Rendered, String> {
+    let nodes = parse_template(&expansion.text)?;
+    let variables = variables_of(&nodes);
+
+    let mut renderer = Renderer {
+        expansion,
+        named: params.named,
+        unnamed: params.unnamed.into_iter(),
+        defaults: variables.named,
+        context,
+        values: HashMap::new(),
+        markers: vec![],
+        inserted: vec![],
+    };
+
+    let mut text = String::new();
+    renderer.render(&nodes, &mut text)?;
+
+    if !renderer.inserted.is_empty() {
+        text = insert_markdown_values(&text, &renderer.inserted, value_index);
+    }
+
+    Ok(Rendered {
+        text,
+        markers: renderer.markers,
+    })
+}
+
+struct Renderer<'a> {
+    expansion: &'a Expansion,
+    named: HashMap<String, String>,
+    unnamed: std::vec::IntoIter<String>,
+    /// The first occurrence of every named variable, for its default.
+    defaults: HashMap<String, Variable>,
+    context: &'a ExpansionContext,
+    /// Built-in variables used more than once are only evaluated once.
+    values: HashMap<String, Option<String>>,
+    /// The markers that were replaced by placeholders so far.
+    markers: Vec<String>,
+    /// Values of Markdown texts that were replaced by placeholders so far.
+    inserted: Vec<String>,
+}
+
+impl Renderer<'_> {
+    fn render(&mut self, nodes: &[Node], text: &mut String) -> Result<(), String> {
+        for node in nodes {
+            match node {
+                Node::Text(literal) => push_text(text, literal),
+                // Formatted text is pasted and looks different in every app,
+                // so there is no way to tell where the marker ends up.
+                Node::Marker(marker) if self.expansion.format != ExpansionFormat::Plain => {
+                    return Err(format!(
+                        "`{}` only works in plain text expansions, not in formatted ones",
+                        marker
+                    ));
+                }
+                Node::Marker(marker) => {
+                    text.push(marker_placeholder(self.markers.len()));
+                    self.markers.push(marker.clone());
+                }
+                Node::Variable(variable) => {
+                    let value = if variable.name.is_empty() {
+                        self.unnamed.next()
+                    } else {
+                        self.value(&variable.name)?
+                    };
+
+                    // Typed variables without a value fail, unless their
+                    // default has the type.
+                    let value = match value {
+                        Some(value) => value,
+                        None => variable.accept(&variable.default)?,
+                    };
+
+                    let value = variable
+                        .filters
+                        .iter()
+                        .fold(value, |value, filter| filter.apply(&value));
+
+                    // Values are inserted as text, not read as markup.
+                    match self.expansion.format {
+                        ExpansionFormat::Plain => push_text(text, &value),
+                        ExpansionFormat::Html => push_text(text, &escape_html(&value)),
+                        // How to escape depends on where the value ends up,
+                        // which is only known once the whole text is there.
+                        ExpansionFormat::Markdown => {
+                            text.push(value_placeholder(self.inserted.len()));
+                            self.inserted
+                                .push(value.chars().filter(|&c| !is_placeholder(c)).collect());
+                        }
+                    }
+                }
+                Node::Section {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let value = self.value(&condition.name)?.unwrap_or_else(|| {
+                        self.defaults
+                            .get(&condition.name)
+                            .map(|variable| variable.default.clone())
+                            .unwrap_or_default()
+                    });
+
+                    if condition.matches(&value) {
+                        self.render(then, text)?;
+                    } else {
+                        self.render(otherwise, text)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The passed or built-in value of a named variable. Passed values of
+    /// choice variables select one of the options.
+    fn value(&mut self, name: &str) -> Result<Option<String>, String> {
+        if let Some(value) = self.values.get(name) {
+            return Ok(value.clone());
+        }
+
+        // Parameters can override built-in variables by their base name,
+        // like `date=tomorrow` for `{date:%A}`.
+        let value = match self
+            .named
+            .get(name)
+            .or_else(|| self.named.get(variable_base(name)))
+        {
+            Some(value) => match self.defaults.get(name) {
+                Some(variable) => Some(variable.accept(value)?),
+                None => Some(value.clone()),
+            },
+            None => self.context.builtin(name, &self.expansion.id),
+        };
+
+        self.values.insert(name.to_string(), value.clone());
+
+        Ok(value)
+    }
+}
@@ -0,0 +1,122 @@
+/// Names of all filters, for error messages.
+const FILTER_NAMES: [&str; 7] = [
+    "upper",
+    "lower",
+    "title",
+    "slug",
+    "urlencode",
+    "trim",
+    "replace:a:b",
+];
+
+/// A transformation of a variable's value, like `upper` in `{name|upper}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    Upper,
+    Lower,
+    /// Capitalize every word.
+    Title,
+    /// Lowercase words joined by dashes, like `hello-world`.
+    Slug,
+    /// Percent-encode everything but letters, digits and `-_.~`.
+    UrlEncode,
+    Trim,
+    /// Replace all occurrences of the first argument with the second.
+    Replace(String, String),
+}
+
+impl Filter {
+    /// Parse a filter with its arguments, like `replace:a:b`.
+    pub fn parse(filter: &str) -> Result<Filter, String> {
+        let mut parts = filter.split(':');
+        let name = parts.next().unwrap_or_default();
+        let args = parts.collect::<Vec<_>>();
+
+        let filter = match (name, args.as_slice()) {
+            ("upper", []) => Filter::Upper,
+            ("lower", []) => Filter::Lower,
+            ("title", []) => Filter::Title,
+            ("slug", []) => Filter::Slug,
+            ("urlencode", []) => Filter::UrlEncode,
+            ("trim", []) => Filter::Trim,
+            ("replace", [from, to]) => Filter::Replace(from.to_string(), to.to_string()),
+            ("replace", _) => {
+                return Err(format!(
+                    "Filter `replace` needs two arguments, like `replace:a:b`, got `{}`",
+                    filter
+                ))
+            }
+            ("upper" | "lower" | "title" | "slug" | "urlencode" | "trim", _) => {
+                return Err(format!("Filter `{}` takes no arguments", name))
+            }
+            _ => {
+                return Err(format!(
+                    "Unknown filter `{}`, available filters are {}",
+                    name,
+                    FILTER_NAMES.join(", ")
+                ))
+            }
+        };
+
+        Ok(filter)
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Filter::Upper => value.to_uppercase(),
+            Filter::Lower => value.to_lowercase(),
+            Filter::Title => title_case(value),
+            Filter::Slug => slug(value),
+            Filter::UrlEncode => url_encode(value),
+            Filter::Trim => value.trim().to_string(),
+            Filter::Replace(from, _) if from.is_empty() => value.to_string(),
+            Filter::Replace(from, to) => value.replace(from.as_str(), to),
+        }
+    }
+}
+
+/// Uppercase the first letter of every word and lowercase the rest.
+fn title_case(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut word_start = true;
+
+    for c in value.chars() {
+        if c.is_whitespace() {
+            word_start = true;
+            result.push(c);
+        } else if word_start {
+            word_start = false;
+            result.extend(c.to_uppercase());
+        } else {
+            result.extend(c.to_lowercase());
+        }
+    }
+
+    result
+}
+
+fn slug(value: &str) -> String {
+    let mut slug = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_end_matches('-').to_string()
+}
+
+fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
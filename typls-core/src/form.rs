@@ -0,0 +1,117 @@
+use serde::Serialize;
+
+use crate::capture::CaptureSignal;
+use crate::context::{is_builtin, ExpansionContext};
+use crate::expand::{find_expansion, split_sequence, Params};
+use crate::settings::AppSettings;
+use crate::variables::{parse_variables, tokenize, Token};
+
+/// A variable of an expansion that can be filled in before it is typed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormField {
+    /// Empty for unnamed variables.
+    pub name: String,
+    pub default: String,
+    /// The value passed after the abbreviation, if any.
+    pub value: Option<String>,
+    /// The options of a choice variable.
+    pub choices: Vec<String>,
+}
+
+/// The variables of a triggered expansion, to ask for their values instead
+/// of requiring them to be typed after the abbreviation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariableForm {
+    pub expansion_id: String,
+    pub abbr: String,
+    /// The named and unnamed variables in order of their first appearance.
+    pub fields: Vec<FormField>,
+    /// The parameters that were typed, kept for built-in variables.
+    #[serde(skip)]
+    params: Params,
+}
+
+impl VariableForm {
+    /// The parameters to render the expansion with, given the submitted
+    /// values of the fields in the same order.
+    pub fn fill(&self, values: Vec<String>) -> Params {
+        let mut params = Params {
+            unnamed: vec![],
+            named: self.params.named.clone(),
+        };
+
+        for (field, value) in self.fields.iter().zip(values) {
+            if field.name.is_empty() {
+                params.unnamed.push(value);
+            } else {
+                params.named.insert(field.name.clone(), value);
+            }
+        }
+
+        params
+    }
+}
+
+/// The form for a confirmed capture. Returns `None` if no expansion matches
+/// or every variable already got a value after the abbreviation. Built-in
+/// variables are not part of the form.
+pub fn variable_form(
+    signal: &CaptureSignal,
+    settings: &AppSettings,
+    context: &ExpansionContext,
+) -> Option<VariableForm> {
+    let (abbr, params) = split_sequence(&signal.sequence, settings);
+    let expansion = find_expansion(abbr, settings, &context.active_app)?;
+
+    // Invalid texts are reported when the expansion is rendered.
+    let tokens = tokenize(&expansion.text).ok()?;
+    let variables = parse_variables(expansion).ok()?;
+
+    let mut fields: Vec<FormField> = vec![];
+    let mut unnamed = params.unnamed.iter();
+
+    for token in tokens {
+        let name = match token {
+            Token::Variable(variable) if variable.name.is_empty() => {
+                fields.push(FormField {
+                    name: String::new(),
+                    default: variable.default,
+                    value: unnamed.next().cloned(),
+                    choices: vec![],
+                });
+                continue;
+            }
+            Token::Variable(variable) => variable.name,
+            Token::If(condition) => condition.name,
+            _ => continue,
+        };
+
+        if is_builtin(&name) || fields.iter().any(|field| field.name == name) {
+            continue;
+        }
+
+        let Some(variable) = variables.named.get(&name) else {
+            continue;
+        };
+
+        fields.push(FormField {
+            value: params.named.get(&name).cloned(),
+            name,
+            default: variable.default.clone(),
+            choices: variable.choices.clone(),
+        });
+    }
+
+    if fields.iter().all(|field| field.value.is_some()) {
+        return None;
+    }
+
+    Some(VariableForm {
+        expansion_id: expansion.id.clone(),
+        abbr: abbr.to_string(),
+        fields,
+        params,
+    })
+}
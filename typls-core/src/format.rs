@@ -0,0 +1,274 @@
+use std::ops::Range;
+
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+use serde::{Deserialize, Serialize};
+
+/// Format of an expansion's text.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ExpansionFormat {
+    #[default]
+    Plain,
+    Markdown,
+    Html,
+}
+
+/// Formatted text with a plain text alternative, for applications that do
+/// not accept rich text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RichText {
+    pub html: String,
+    pub plain: String,
+}
+
+/// Convert a rendered expansion text into rich text. Returns `None` for plain
+/// text.
+pub fn to_rich_text(format: ExpansionFormat, text: &str) -> Option<RichText> {
+    let html = match format {
+        ExpansionFormat::Plain => return None,
+        ExpansionFormat::Markdown => markdown_to_html(text),
+        ExpansionFormat::Html => text.to_string(),
+    };
+
+    let plain = html_to_plain(&html);
+
+    Some(RichText { html, plain })
+}
+
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut html = String::new();
+    html::push_html(&mut html, markdown_parser(markdown));
+
+    html.trim_end().to_string()
+}
+
+fn markdown_parser(markdown: &str) -> Parser<'_> {
+    Parser::new_ext(
+        markdown,
+        Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES,
+    )
+}
+
+/// Where a value ends up in a Markdown text, which decides how it has to be
+/// escaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MarkdownContext {
+    Text,
+    /// Code spans and blocks, where backslashes are typed as they are.
+    Code,
+    /// Raw HTML, which is passed on without being read as Markdown.
+    Html,
+}
+
+/// Insert values into a Markdown text that has placeholders in their place.
+/// `index_of` tells the index of the value a character stands for. Values
+/// are escaped for where they end up: with backslashes in text, as HTML in
+/// raw HTML and not at all in code, which has no escapes.
+pub(crate) fn insert_markdown_values(
+    markdown: &str,
+    values: &[String],
+    index_of: impl Fn(char) -> Option<usize>,
+) -> String {
+    let mut contexts: Vec<(Range<usize>, MarkdownContext)> = vec![];
+
+    for (event, range) in markdown_parser(markdown).into_offset_iter() {
+        match event {
+            Event::Code(_) | Event::Start(Tag::CodeBlock(_)) => {
+                contexts.push((range, MarkdownContext::Code))
+            }
+            Event::Html(_) | Event::InlineHtml(_) => contexts.push((range, MarkdownContext::Html)),
+            _ => (),
+        }
+    }
+
+    let mut text = String::with_capacity(markdown.len());
+
+    for (offset, c) in markdown.char_indices() {
+        let Some(value) = index_of(c).and_then(|index| values.get(index)) else {
+            text.push(c);
+            continue;
+        };
+
+        let context = contexts
+            .iter()
+            .find(|(range, _)| range.contains(&offset))
+            .map_or(MarkdownContext::Text, |(_, context)| *context);
+
+        match context {
+            MarkdownContext::Text => text.push_str(&escape_markdown(value)),
+            MarkdownContext::Code => text.push_str(value),
+            MarkdownContext::Html => text.push_str(&escape_html(value)),
+        }
+    }
+
+    text
+}
+
+/// Reduce HTML to readable plain text. Block elements and line breaks become
+/// new lines, paragraphs are separated by an empty line, list items are
+/// prefixed with a dash and whitespace is collapsed like a browser would,
+/// except in `<pre>` blocks.
+pub fn html_to_plain(html: &str) -> String {
+    let mut plain = String::new();
+    let mut chars = html.chars().peekable();
+    // Number of open `<pre>` elements.
+    let mut pre_depth = 0_usize;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '<' => {
+                let mut tag = String::new();
+                for c in chars.by_ref() {
+                    if c == '>' {
+                        break;
+                    }
+                    tag.push(c);
+                }
+
+                let is_closing = tag.starts_with('/');
+                let name = tag
+                    .trim_start_matches('/')
+                    .split(|c: char| c.is_whitespace() || c == '/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_lowercase();
+
+                match name.as_str() {
+                    "br" => {
+                        trim_trailing_spaces(&mut plain);
+                        plain.push('\n');
+                    }
+                    "li" if !is_closing => {
+                        end_line(&mut plain, 1);
+                        plain.push_str("- ");
+                    }
+                    "div" | "li" | "tr" => end_line(&mut plain, 1),
+                    "pre" => {
+                        end_line(&mut plain, 2);
+
+                        if is_closing {
+                            pre_depth = pre_depth.saturating_sub(1);
+                        } else {
+                            pre_depth += 1;
+
+                            // Like browsers, ignore a new line right after
+                            // the opening tag.
+                            if chars.peek() == Some(&'\n') {
+                                chars.next();
+                            }
+                        }
+                    }
+                    "p" | "ul" | "ol" | "blockquote" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                        end_line(&mut plain, 2)
+                    }
+                    _ => (),
+                }
+            }
+            '&' => {
+                let mut entity = String::new();
+                let mut is_terminated = false;
+
+                while let Some(&c) = chars.peek() {
+                    if c == ';' {
+                        chars.next();
+                        is_terminated = true;
+                        break;
+                    }
+
+                    if !(c.is_ascii_alphanumeric() || c == '#') || entity.len() > 8 {
+                        break;
+                    }
+
+                    entity.push(c);
+                    chars.next();
+                }
+
+                match decode_entity(&entity).filter(|_| is_terminated) {
+                    Some(decoded) => plain.push(decoded),
+                    None => {
+                        plain.push('&');
+                        plain.push_str(&entity);
+                        if is_terminated {
+                            plain.push(';');
+                        }
+                    }
+                }
+            }
+            c if c.is_whitespace() && pre_depth > 0 => plain.push(c),
+            c if c.is_whitespace() => {
+                if !plain.is_empty() && !plain.ends_with(char::is_whitespace) {
+                    plain.push(' ');
+                }
+            }
+            c => plain.push(c),
+        }
+    }
+
+    // Spaces at the end of lines were already removed, and collapsed
+    // whitespace never starts a line.
+    plain.trim_start_matches('\n').trim_end().to_string()
+}
+
+/// Make sure the text ends with the given number of new lines, so blocks
+/// start on their own line and paragraphs are separated by an empty one.
+fn end_line(plain: &mut String, newlines: usize) {
+    if plain.is_empty() {
+        return;
+    }
+
+    trim_trailing_spaces(plain);
+
+    let existing = plain.len() - plain.trim_end_matches('\n').len();
+    for _ in existing..newlines {
+        plain.push('\n');
+    }
+}
+
+fn trim_trailing_spaces(plain: &mut String) {
+    let trimmed_len = plain.trim_end_matches(' ').len();
+    plain.truncate(trimmed_len);
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = if let Some(hex) = entity.strip_prefix("#x") {
+                u32::from_str_radix(hex, 16).ok()?
+            } else {
+                entity.strip_prefix('#')?.parse().ok()?
+            };
+
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Escape text to be inserted into Markdown, outside of code and raw HTML.
+/// Every ASCII punctuation character can be escaped with a backslash.
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::new();
+
+    for c in text.chars() {
+        if c.is_ascii_punctuation() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Escape text to be inserted into HTML.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
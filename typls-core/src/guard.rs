@@ -0,0 +1,77 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long key presses are still ignored after typls finished typing.
+/// Simulated events can reach the listener with a small delay.
+pub const DEFAULT_INJECTION_GRACE: Duration = Duration::from_millis(100);
+
+/// Tracks when typls itself is typing, so the listener can ignore the
+/// simulated key presses instead of capturing its own output. Clones share
+/// the same state.
+#[derive(Debug, Clone)]
+pub struct InjectionGuard {
+    state: Arc<Mutex<GuardState>>,
+    grace: Duration,
+}
+
+#[derive(Debug, Default)]
+struct GuardState {
+    injecting: bool,
+    ended_at: Option<Instant>,
+}
+
+impl Default for InjectionGuard {
+    fn default() -> Self {
+        InjectionGuard::new(DEFAULT_INJECTION_GRACE)
+    }
+}
+
+impl InjectionGuard {
+    pub fn new(grace: Duration) -> InjectionGuard {
+        InjectionGuard {
+            state: Arc::new(Mutex::new(GuardState::default())),
+            grace,
+        }
+    }
+
+    /// Start typing. The returned value ends the injection when dropped.
+    /// Returns `None` if an injection is already running, so an expansion
+    /// can never be triggered by the output of another one.
+    pub fn begin(&self) -> Option<Injection> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.injecting {
+            return None;
+        }
+
+        state.injecting = true;
+
+        Some(Injection {
+            guard: self.clone(),
+        })
+    }
+
+    /// Whether key presses should currently be ignored.
+    pub fn is_active(&self) -> bool {
+        let state = self.state.lock().unwrap();
+
+        state.injecting
+            || state
+                .ended_at
+                .is_some_and(|ended_at| ended_at.elapsed() < self.grace)
+    }
+}
+
+/// A running injection, see [`InjectionGuard::begin`].
+#[derive(Debug)]
+pub struct Injection {
+    guard: InjectionGuard,
+}
+
+impl Drop for Injection {
+    fn drop(&mut self) {
+        let mut state = self.guard.state.lock().unwrap();
+        state.injecting = false;
+        state.ended_at = Some(Instant::now());
+    }
+}
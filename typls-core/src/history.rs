@@ -0,0 +1,149 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::capture::CaptureSignal;
+use crate::expand::{split_sequence, ActiveApp, Params};
+use crate::format::RichText;
+use crate::plan::ExpansionPlan;
+use crate::settings::{AppSettings, OutputKind};
+
+/// A performed expansion.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: String,
+    pub expansion_id: String,
+    pub abbr: String,
+    pub params: Params,
+    /// The rendered text, as it was typed.
+    pub text: String,
+    pub rich: Option<RichText>,
+    pub output: OutputKind,
+    /// Process path of the application the expansion was typed into.
+    pub app: String,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        signal: &CaptureSignal,
+        plan: &ExpansionPlan,
+        settings: &AppSettings,
+        active_app: &ActiveApp,
+    ) -> HistoryEntry {
+        let (abbr, params) = split_sequence(&signal.sequence, settings);
+
+        HistoryEntry {
+            id: Uuid::new_v4().to_string(),
+            expansion_id: plan.expansion_id.clone(),
+            abbr: abbr.to_string(),
+            params,
+            text: plan.text.clone(),
+            rich: plan.rich.clone(),
+            output: plan.output,
+            app: active_app.process_path.clone(),
+            timestamp: unix_timestamp(),
+        }
+    }
+
+    /// Plan to type the text of this entry again at the cursor.
+    pub fn plan(&self) -> ExpansionPlan {
+        ExpansionPlan {
+            expansion_id: self.expansion_id.clone(),
+            group: None,
+            erase: 0,
+            text: self.text.clone(),
+            keys: vec![],
+            rich: self.rich.clone(),
+            output: self.output,
+            snippet: None,
+        }
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+
+        self.abbr.to_lowercase().contains(&query)
+            || self.text.to_lowercase().contains(&query)
+            || self
+                .params
+                .unnamed
+                .iter()
+                .chain(self.params.named.values())
+                .any(|value| value.to_lowercase().contains(&query))
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub(crate) fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// The most recent expansions, oldest first.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct History {
+    entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    /// Load a history previously written with [`History::save`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<History, String> {
+        let contents = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+
+        serde_json::from_str(&contents).map_err(|error| format!("Invalid history: {}", error))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|error| error.to_string())?;
+
+        std::fs::write(path, json).map_err(|error| error.to_string())
+    }
+
+    /// Add an entry and drop the oldest ones beyond `limit`.
+    pub fn push(&mut self, entry: HistoryEntry, limit: usize) {
+        self.entries.push(entry);
+        self.truncate(limit);
+    }
+
+    /// Keep only the `limit` most recent entries.
+    pub fn truncate(&mut self, limit: usize) {
+        let excess = self.entries.len().saturating_sub(limit);
+        self.entries.drain(..excess);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// All entries, most recent first.
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.iter().rev().cloned().collect()
+    }
+
+    /// Entries whose abbreviation, parameters or text contain the query,
+    /// ignoring case. Most recent first.
+    pub fn search(&self, query: &str) -> Vec<HistoryEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.matches(query))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+}
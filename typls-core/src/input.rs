@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Keys the capture state machine reacts to. All other keys are `Other` and
+/// only matter through the text they produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Key {
+    Return,
+    LeftArrow,
+    RightArrow,
+    Escape,
+    Backspace,
+    Tab,
+    /// Function keys, like `F1`.
+    Function(u8),
+    /// Shift, control, alt and meta keys.
+    Modifier,
+    Other,
+}
+
+/// A single key press and the text it produced, if any.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyEvent {
+    pub key: Key,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+impl KeyEvent {
+    /// A press of a key that does not produce any text.
+    pub fn key(key: Key) -> KeyEvent {
+        KeyEvent { key, name: None }
+    }
+
+    /// A press of a key that produced the given text.
+    pub fn text(name: &str) -> KeyEvent {
+        KeyEvent {
+            key: Key::Other,
+            name: Some(name.to_string()),
+        }
+    }
+}
+
+/// Something that produces key presses, like a global keyboard hook.
+pub trait InputSource {
+    /// Call `callback` for every key press. Blocks until the source is
+    /// exhausted or fails.
+    fn listen(&mut self, callback: Box<dyn FnMut(KeyEvent)>) -> Result<(), String>;
+}
+
+/// Replays previously recorded key presses. Useful to drive the capture
+/// state machine deterministically.
+#[derive(Debug, Default)]
+pub struct ReplaySource {
+    events: Vec<KeyEvent>,
+}
+
+impl ReplaySource {
+    pub fn new(events: Vec<KeyEvent>) -> ReplaySource {
+        ReplaySource { events }
+    }
+
+    /// Load events from a file containing one JSON encoded `KeyEvent` per
+    /// line, like `{"key":"other","name":"a"}`. Empty lines are skipped.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<ReplaySource, String> {
+        let contents = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+
+        let events = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line)
+                    .map_err(|error| format!("Invalid event on line {}: {}", index + 1, error))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ReplaySource::new(events))
+    }
+}
+
+impl InputSource for ReplaySource {
+    fn listen(&mut self, mut callback: Box<dyn FnMut(KeyEvent)>) -> Result<(), String> {
+        for event in self.events.drain(..) {
+            callback(event);
+        }
+
+        Ok(())
+    }
+}
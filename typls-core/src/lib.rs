@@ -0,0 +1,63 @@
+//! The expansion engine of typls.
+//!
+//! This crate owns the settings model, matching of abbreviations and rendering
+//! of expansion texts. It has no Tauri or keyboard dependencies, so the desktop
+//! app only has to wire keyboard input and output to it.
+
+pub mod capture;
+pub mod context;
+pub mod expand;
+pub mod filters;
+pub mod form;
+pub mod format;
+pub mod guard;
+pub mod history;
+pub mod input;
+pub mod output;
+pub mod phrases;
+pub mod plan;
+pub mod session;
+pub mod settings;
+pub mod shell;
+pub mod stats;
+pub mod types;
+pub mod undo;
+pub mod variables;
+
+pub use capture::{Capture, CaptureSignal, Signal};
+pub use context::{
+    is_builtin, variable_base, ExpansionContext, SystemSource, BUILTIN_VARIABLES,
+    MAX_CLIPBOARD_LENGTH,
+};
+pub use expand::{
+    expand, find_expansion, find_unambiguous_expansion, render, split_sequence, ActiveApp, Params,
+};
+pub use filters::Filter;
+pub use form::{variable_form, FormField, VariableForm};
+pub use format::{to_rich_text, ExpansionFormat, RichText};
+pub use guard::{Injection, InjectionGuard, DEFAULT_INJECTION_GRACE};
+pub use history::{History, HistoryEntry};
+pub use input::{InputSource, Key, KeyEvent, ReplaySource};
+pub use output::{
+    execute, execute_caret_move, execute_undo, OutputAction, OutputSink, RecordingSink,
+};
+pub use phrases::{PhraseAnalyzer, PhraseSuggestion};
+pub use plan::{
+    grapheme_count, plan_expansion, plan_form, plan_selection, resolve_output, ExpansionPlan,
+    PlanKey,
+};
+pub use session::{CaretMove, SessionUpdate, SnippetSession};
+pub use settings::{
+    default_settings, App, AppSettings, ConfirmSettings, Expansion, Group, HistoryMode,
+    HistorySettings, InputBackend, InputSettings, OutputKind, OutputSettings, SelectionHotkey,
+    SelectionSettings, ShellSettings, SnippetSettings, SuggestionSettings, TriggerSettings,
+    UndoSettings, VariableSettings, DEFAULT_CONFIRM_CHARS,
+};
+pub use shell::{run_command, ShellPermissions};
+pub use stats::{ExpansionStats, ExpansionUsage, StatsSummary, UsageStats};
+pub use types::VariableType;
+pub use undo::{LastExpansion, UndoPlan, UndoWindow};
+pub use variables::{
+    has_variables, parse_template, parse_variables, tokenize, Condition, ExpansionVariables, Node,
+    Token, Variable, CURSOR_MARKER,
+};
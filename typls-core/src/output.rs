@@ -0,0 +1,109 @@
+use crate::format::RichText;
+use crate::plan::{ExpansionPlan, PlanKey};
+use crate::session::CaretMove;
+use crate::undo::UndoPlan;
+
+/// Something that carries out expansions, like a simulated keyboard.
+pub trait OutputSink {
+    /// Remove the given number of characters before the cursor.
+    fn erase(&mut self, count: usize) -> Result<(), String>;
+
+    /// Insert text at the cursor.
+    fn type_text(&mut self, text: &str) -> Result<(), String>;
+
+    /// Press and release a single key.
+    fn press(&mut self, key: PlanKey) -> Result<(), String>;
+
+    /// Insert formatted text at the cursor. Sinks that do not support
+    /// formatting insert the plain text.
+    fn type_rich_text(&mut self, rich: &RichText) -> Result<(), String> {
+        self.type_text(&rich.plain)
+    }
+}
+
+/// Carry out the plan on the given sink.
+pub fn execute(plan: &ExpansionPlan, sink: &mut dyn OutputSink) -> Result<(), String> {
+    sink.erase(plan.erase)?;
+
+    match &plan.rich {
+        Some(rich) => sink.type_rich_text(rich)?,
+        None => sink.type_text(&plan.text)?,
+    }
+
+    for key in plan.keys.iter() {
+        sink.press(*key)?;
+    }
+
+    Ok(())
+}
+
+/// Move the caret to another tab stop on the given sink.
+pub fn execute_caret_move(caret_move: &CaretMove, sink: &mut dyn OutputSink) -> Result<(), String> {
+    if caret_move.erase > 0 {
+        sink.erase(caret_move.erase)?;
+    }
+
+    for _ in 0..caret_move.left {
+        sink.press(PlanKey::Left)?;
+    }
+
+    for _ in 0..caret_move.right {
+        sink.press(PlanKey::Right)?;
+    }
+
+    Ok(())
+}
+
+/// Revert an expansion on the given sink.
+pub fn execute_undo(undo: &UndoPlan, sink: &mut dyn OutputSink) -> Result<(), String> {
+    for _ in 0..undo.right {
+        sink.press(PlanKey::Right)?;
+    }
+
+    sink.erase(undo.erase)?;
+    sink.type_text(&undo.text)
+}
+
+/// A single operation performed on a sink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputAction {
+    Erase(usize),
+    Text(String),
+    RichText(RichText),
+    Press(PlanKey),
+}
+
+/// Records everything instead of performing it. Useful for tests and dry
+/// runs.
+#[derive(Debug, Default)]
+pub struct RecordingSink {
+    pub actions: Vec<OutputAction>,
+}
+
+impl RecordingSink {
+    pub fn new() -> RecordingSink {
+        RecordingSink::default()
+    }
+}
+
+impl OutputSink for RecordingSink {
+    fn erase(&mut self, count: usize) -> Result<(), String> {
+        self.actions.push(OutputAction::Erase(count));
+        Ok(())
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<(), String> {
+        self.actions.push(OutputAction::Text(text.to_string()));
+        Ok(())
+    }
+
+    fn press(&mut self, key: PlanKey) -> Result<(), String> {
+        self.actions.push(OutputAction::Press(key));
+        Ok(())
+    }
+
+    fn type_rich_text(&mut self, rich: &RichText) -> Result<(), String> {
+        self.actions.push(OutputAction::RichText(rich.clone()));
+        Ok(())
+    }
+}
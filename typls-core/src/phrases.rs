@@ -0,0 +1,284 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::input::{Key, KeyEvent};
+use crate::settings::{AppSettings, Expansion};
+
+/// Shortest phrase that is counted, in words.
+const MIN_PHRASE_WORDS: usize = 3;
+
+/// Longest phrase that is counted, in words.
+const MAX_PHRASE_WORDS: usize = 8;
+
+/// Shortest phrase that is worth an abbreviation, in characters.
+const MIN_PHRASE_CHARS: usize = 12;
+
+/// Phrases seen only once are dropped once this many are counted.
+const MAX_COUNTED_PHRASES: usize = 50_000;
+
+/// Number of finished words after which the counts should be saved.
+const SAVE_INTERVAL: usize = 100;
+
+/// A frequently typed phrase, proposed as a new expansion.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhraseSuggestion {
+    pub expansion: Expansion,
+    /// How often the phrase was typed.
+    pub count: u32,
+}
+
+/// Finds phrases the user types repeatedly. Only salted hashes of the
+/// phrases and their counts are kept, so the saved state does not reveal what
+/// was typed. The salt is not saved with them, otherwise the phrases could be
+/// found by hashing guesses. The text of a phrase is only remembered in memory
+/// once it was typed often enough to become a suggestion.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhraseAnalyzer {
+    #[serde(skip)]
+    salt: String,
+    counts: HashMap<String, u32>,
+    #[serde(skip)]
+    words: VecDeque<String>,
+    #[serde(skip)]
+    current_word: String,
+    #[serde(skip)]
+    candidates: HashMap<String, String>,
+    #[serde(skip)]
+    unsaved_words: usize,
+}
+
+impl Default for PhraseAnalyzer {
+    fn default() -> Self {
+        PhraseAnalyzer {
+            salt: Uuid::new_v4().to_string(),
+            counts: HashMap::new(),
+            words: VecDeque::new(),
+            current_word: String::new(),
+            candidates: HashMap::new(),
+            unsaved_words: 0,
+        }
+    }
+}
+
+impl PhraseAnalyzer {
+    /// Start counting with a new random salt.
+    pub fn new() -> PhraseAnalyzer {
+        PhraseAnalyzer::default()
+    }
+
+    /// Start counting with a salt from a previous [`PhraseAnalyzer::salt`].
+    pub fn with_salt(salt: &str) -> PhraseAnalyzer {
+        PhraseAnalyzer {
+            salt: salt.to_string(),
+            ..PhraseAnalyzer::default()
+        }
+    }
+
+    /// Load counts previously written with [`PhraseAnalyzer::save`]. They are
+    /// only found again with the salt they were counted with.
+    pub fn from_file(path: impl AsRef<Path>, salt: &str) -> Result<PhraseAnalyzer, String> {
+        let contents = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+
+        let analyzer: PhraseAnalyzer = serde_json::from_str(&contents)
+            .map_err(|error| format!("Invalid phrase counts: {}", error))?;
+
+        Ok(PhraseAnalyzer {
+            salt: salt.to_string(),
+            ..analyzer
+        })
+    }
+
+    /// The salt of the hashes. It has to be kept apart from the saved counts.
+    pub fn salt(&self) -> &str {
+        &self.salt
+    }
+
+    /// Write the hashed counts. Neither typed text nor the salt are written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|error| error.to_string())?;
+
+        std::fs::write(path, json).map_err(|error| error.to_string())
+    }
+
+    /// Whether enough was typed to save the counts again. Returns true only
+    /// once per hundred finished words, so each save is requested once.
+    pub fn take_save_request(&mut self) -> bool {
+        if self.unsaved_words < SAVE_INTERVAL {
+            return false;
+        }
+
+        self.unsaved_words = 0;
+        true
+    }
+
+    /// Feed a typed key. Phrases need to be typed `min_count` times to become
+    /// a suggestion.
+    pub fn handle(&mut self, event: &KeyEvent, min_count: u32) {
+        match event.key {
+            Key::Modifier | Key::Function(_) => (),
+            Key::Backspace => {
+                if self.current_word.pop().is_none() {
+                    // Editing a previous word, start over.
+                    self.words.clear();
+                }
+            }
+            Key::Other | Key::Tab => match event.name.as_deref() {
+                Some(" ") => self.end_word(min_count),
+                Some(text) if !text.is_empty() && text.chars().all(is_word_char) => {
+                    self.current_word.push_str(text);
+                }
+                // Punctuation, line breaks and shortcuts end the phrase.
+                Some(_) => self.end_phrase(min_count),
+                // Keys without text move the caret somewhere else.
+                None => self.reset(),
+            },
+            Key::Return => self.end_phrase(min_count),
+            Key::LeftArrow | Key::RightArrow | Key::Escape => self.reset(),
+        }
+    }
+
+    /// Frequent phrases as new expansions, most frequent first. Phrases that
+    /// already are the text of an expansion are skipped.
+    pub fn suggestions(&self, settings: &AppSettings, min_count: u32) -> Vec<PhraseSuggestion> {
+        let mut phrases = self
+            .candidates
+            .iter()
+            .filter_map(|(hash, phrase)| {
+                let count = *self.counts.get(hash)?;
+                (count >= min_count).then_some((phrase, count))
+            })
+            .filter(|(phrase, _)| !settings.expansions.iter().any(|e| e.text == **phrase))
+            .collect::<Vec<_>>();
+
+        // Parts of a longer phrase that is typed just as often are only
+        // typed as part of it.
+        let longer = phrases.clone();
+        phrases.retain(|(phrase, count)| {
+            !longer.iter().any(|(other, other_count)| {
+                other_count >= count
+                    && other.len() > phrase.len()
+                    && other.contains(phrase.as_str())
+            })
+        });
+
+        // Longer phrases first when counts are equal, they save more typing.
+        phrases.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.len().cmp(&a.0.len())));
+
+        let mut abbrs = settings
+            .expansions
+            .iter()
+            .map(|expansion| expansion.abbr.clone())
+            .collect::<Vec<_>>();
+
+        phrases
+            .into_iter()
+            .map(|(phrase, count)| {
+                let abbr = generate_abbr(phrase, &abbrs);
+                abbrs.push(abbr.clone());
+
+                PhraseSuggestion {
+                    expansion: Expansion {
+                        id: Uuid::new_v4().to_string(),
+                        abbr,
+                        text: phrase.clone(),
+                        ..Default::default()
+                    },
+                    count,
+                }
+            })
+            .collect()
+    }
+
+    /// Forget the typed words that are not part of a phrase yet.
+    pub fn reset(&mut self) {
+        self.words.clear();
+        self.current_word.clear();
+    }
+
+    fn end_word(&mut self, min_count: u32) {
+        if self.current_word.is_empty() {
+            return;
+        }
+
+        let word = std::mem::take(&mut self.current_word);
+        self.words.push_back(word);
+        if self.words.len() > MAX_PHRASE_WORDS {
+            self.words.pop_front();
+        }
+
+        self.unsaved_words += 1;
+
+        // Count every phrase ending with this word.
+        for length in MIN_PHRASE_WORDS..=self.words.len() {
+            let phrase = self
+                .words
+                .range(self.words.len() - length..)
+                .map(|word| word.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            if phrase.chars().count() < MIN_PHRASE_CHARS {
+                continue;
+            }
+
+            let hash = self.hash(&phrase);
+            let count = self.counts.entry(hash.clone()).or_default();
+            *count += 1;
+
+            if *count >= min_count {
+                self.candidates.insert(hash, phrase);
+            }
+        }
+
+        if self.counts.len() > MAX_COUNTED_PHRASES {
+            self.counts.retain(|_, count| *count > 1);
+        }
+    }
+
+    fn end_phrase(&mut self, min_count: u32) {
+        self.end_word(min_count);
+        self.reset();
+    }
+
+    fn hash(&self, phrase: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        hasher.update(phrase.to_lowercase().as_bytes());
+
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '\'' || c == '-'
+}
+
+/// Build an abbreviation from the first letters of the words, like `brfb` for
+/// "Best regards from Berlin". A number is added if it is already taken.
+fn generate_abbr(phrase: &str, taken: &[String]) -> String {
+    let initials = phrase
+        .split_whitespace()
+        .filter_map(|word| word.chars().find(|c| c.is_alphanumeric()))
+        .flat_map(|c| c.to_lowercase())
+        .collect::<String>();
+
+    let mut abbr = initials.clone();
+    let mut number = 2;
+
+    while taken.contains(&abbr) {
+        abbr = format!("{}{}", initials, number);
+        number += 1;
+    }
+
+    abbr
+}
@@ -0,0 +1,211 @@
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::capture::CaptureSignal;
+use crate::context::ExpansionContext;
+use crate::expand::{marker_index, resolve, resolve_with, split_sequence, Params, Rendered};
+use crate::format::{escape_html, to_rich_text, ExpansionFormat, RichText};
+use crate::session::SnippetSession;
+use crate::settings::{AppSettings, Expansion, OutputKind};
+
+/// Keys that are pressed after the text was typed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PlanKey {
+    Return,
+    Left,
+    Right,
+}
+
+/// Everything needed to carry out an expansion, without performing it.
+/// Executors turn this into actual key presses (or anything else).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpansionPlan {
+    pub expansion_id: String,
+    pub group: Option<String>,
+    /// Number of characters to erase before typing, covering the trigger,
+    /// the captured sequence and whatever confirmed it.
+    pub erase: usize,
+    /// The rendered text, including the appended confirm character.
+    pub text: String,
+    /// Keys to press after typing the text.
+    pub keys: Vec<PlanKey>,
+    /// Formatted version of `text` for expansions that are not plain text.
+    pub rich: Option<RichText>,
+    /// How the plan should be carried out.
+    pub output: OutputKind,
+    /// The remaining tab stops, if the text has more than one.
+    pub snippet: Option<SnippetSession>,
+}
+
+/// Decide what should happen for a confirmed capture. Returns `None` if no
+/// expansion matches the captured abbreviation.
+pub fn plan_expansion(
+    signal: &CaptureSignal,
+    settings: &AppSettings,
+    context: &ExpansionContext,
+) -> Option<ExpansionPlan> {
+    let (expansion, rendered) = resolve(&signal.sequence, settings, context)?;
+
+    Some(plan_text(signal, settings, expansion, rendered))
+}
+
+/// Plan an expansion with the values entered in its variable form instead
+/// of the parameters typed after the abbreviation.
+pub fn plan_form(
+    signal: &CaptureSignal,
+    params: Params,
+    settings: &AppSettings,
+    context: &ExpansionContext,
+) -> Option<ExpansionPlan> {
+    let (abbr, _) = split_sequence(&signal.sequence, settings);
+    let (expansion, rendered) = resolve_with(abbr, params, settings, context)?;
+
+    Some(plan_text(signal, settings, expansion, rendered))
+}
+
+fn plan_text(
+    signal: &CaptureSignal,
+    settings: &AppSettings,
+    expansion: &Expansion,
+    rendered: Rendered,
+) -> ExpansionPlan {
+    let Rendered { text, markers } = rendered;
+
+    // Erase what was actually typed: the trigger, the sequence and the
+    // confirm character or enter key. Backspace removes a whole grapheme, so
+    // count those instead of bytes or chars.
+    let erase = grapheme_count(&settings.trigger.string)
+        + grapheme_count(&signal.sequence)
+        + grapheme_count(&signal.append)
+        + if signal.append_enter { 1 } else { 0 };
+
+    let output = resolve_output(settings, expansion, &strip_placeholders(&text));
+
+    let rich = to_rich_text(expansion.format, &text).map(|rich| RichText {
+        html: format!("{}{}", rich.html, escape_html(&signal.append)),
+        plain: format!("{}{}", rich.plain, signal.append),
+    });
+    let mut text = match &rich {
+        Some(rich) => rich.plain.clone(),
+        None => format!("{}{}", text, signal.append),
+    };
+
+    // Remove the cursor marker and tab stops and remember where they were.
+    // Every grapheme, including line breaks, is one press of an arrow key.
+    // Formatted texts have none, they are rejected while rendering.
+    let stops = take_markers(&mut text, &markers);
+
+    let enter = if signal.append_enter { 1 } else { 0 };
+    let len = grapheme_count(&text) + enter;
+
+    let mut keys = vec![];
+    if signal.append_enter {
+        keys.push(PlanKey::Return);
+    }
+
+    let mut snippet = None;
+    if let Some((&first, rest)) = stops.split_first() {
+        keys.extend(std::iter::repeat_n(PlanKey::Left, len - first));
+        snippet = SnippetSession::new(first, rest.to_vec(), len, output);
+    }
+
+    ExpansionPlan {
+        expansion_id: expansion.id.clone(),
+        group: expansion.group.clone(),
+        erase,
+        text,
+        keys,
+        rich,
+        output,
+        snippet,
+    }
+}
+
+/// Plan an expansion that replaces the selected text instead of a typed
+/// abbreviation. Nothing is erased, typing replaces the selection.
+pub fn plan_selection(
+    sequence: &str,
+    settings: &AppSettings,
+    context: &ExpansionContext,
+) -> Option<ExpansionPlan> {
+    let signal = CaptureSignal {
+        sequence: sequence.to_string(),
+        append: String::new(),
+        append_enter: false,
+    };
+
+    let plan = plan_expansion(&signal, settings, context)?;
+
+    Some(ExpansionPlan { erase: 0, ..plan })
+}
+
+/// Pick the output for an expansion. The expansion's own output wins, then
+/// the one of its group and finally the global one. Long texts are pasted
+/// instead of typed if a paste threshold is set. Rich text can only be
+/// pasted.
+pub fn resolve_output(settings: &AppSettings, expansion: &Expansion, text: &str) -> OutputKind {
+    if expansion.format != ExpansionFormat::Plain {
+        return OutputKind::Clipboard;
+    }
+
+    if let Some(output) = expansion.output {
+        return output;
+    }
+
+    let output = expansion
+        .group
+        .as_ref()
+        .and_then(|group_id| settings.groups.as_ref()?.iter().find(|g| g.id == *group_id))
+        .and_then(|group| group.output)
+        .unwrap_or(settings.output.kind);
+
+    match settings.output.paste_threshold {
+        Some(threshold) if grapheme_count(text) >= threshold => OutputKind::Clipboard,
+        _ => output,
+    }
+}
+
+/// Remove the placeholders of all cursor markers and tab stops from the
+/// text. Returns their positions in graphemes from the start, in the order
+/// they are visited: `{$1}`, `{$2}`, ... and finally `{|}` or `{$0}`. Only the
+/// first marker of each number is used.
+fn take_markers(text: &mut String, markers: &[String]) -> Vec<usize> {
+    let mut stripped = String::with_capacity(text.len());
+    let mut stops: Vec<(usize, usize)> = vec![];
+
+    for c in text.chars() {
+        let Some(marker) = marker_index(c) else {
+            stripped.push(c);
+            continue;
+        };
+
+        let order = markers[marker]
+            .strip_prefix("{$")
+            .and_then(|number| number.strip_suffix('}')?.parse().ok())
+            .filter(|&number| number > 0)
+            .unwrap_or(usize::MAX);
+
+        if !stops.iter().any(|(existing, _)| *existing == order) {
+            stops.push((order, grapheme_count(&stripped)));
+        }
+    }
+
+    *text = stripped;
+
+    stops.sort_by_key(|(order, _)| *order);
+    stops.into_iter().map(|(_, position)| position).collect()
+}
+
+/// The text without marker placeholders.
+fn strip_placeholders(text: &str) -> String {
+    text.chars()
+        .filter(|&c| marker_index(c).is_none())
+        .collect()
+}
+
+/// Number of user-perceived characters in the text.
+pub fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
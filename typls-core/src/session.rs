@@ -0,0 +1,162 @@
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::input::{Key, KeyEvent};
+use crate::settings::OutputKind;
+
+/// Moves the caret within the active snippet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaretMove {
+    /// Characters to erase first, like the tab typed by the hotkey.
+    pub erase: usize,
+    pub left: usize,
+    pub right: usize,
+    /// The output of the expansion the snippet belongs to.
+    pub output: OutputKind,
+}
+
+/// A typed expansion with tab stops that are still to be visited. It follows
+/// the typing of the user to know where the caret and the stops are. All
+/// positions are in graphemes from the start of the expansion.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetSession {
+    caret: usize,
+    stops: Vec<usize>,
+    len: usize,
+    output: OutputKind,
+}
+
+/// Result of feeding a key press into a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionUpdate {
+    /// The key press was tracked.
+    Continue,
+    /// The caret position is no longer known, so the session has to end.
+    End,
+    /// The next stop was requested.
+    Move(CaretMove),
+}
+
+impl SnippetSession {
+    /// Start a session with the caret at the first stop. Returns `None` if
+    /// there are no further stops to visit.
+    pub fn new(caret: usize, stops: Vec<usize>, len: usize, output: OutputKind) -> Option<Self> {
+        if stops.is_empty() {
+            return None;
+        }
+
+        Some(SnippetSession {
+            caret,
+            stops,
+            len,
+            output,
+        })
+    }
+
+    /// Whether all stops have been visited.
+    pub fn is_finished(&self) -> bool {
+        self.stops.is_empty()
+    }
+
+    /// Track a key press. `next_key` is the hotkey to move to the next stop.
+    pub fn handle(&mut self, event: &KeyEvent, next_key: Key) -> SessionUpdate {
+        if event.key == next_key {
+            // The hotkey may have typed something itself (like a tab).
+            let erase = event
+                .name
+                .as_deref()
+                .map(|name| name.graphemes(true).count())
+                .unwrap_or_default();
+
+            return match self.next_stop(erase) {
+                Some(caret_move) => SessionUpdate::Move(caret_move),
+                None => SessionUpdate::End,
+            };
+        }
+
+        match event.key {
+            Key::Backspace => {
+                if self.caret == 0 {
+                    return SessionUpdate::End;
+                }
+
+                self.remove(1);
+                SessionUpdate::Continue
+            }
+            Key::LeftArrow => {
+                if self.caret == 0 {
+                    return SessionUpdate::End;
+                }
+
+                self.caret -= 1;
+                SessionUpdate::Continue
+            }
+            Key::RightArrow => {
+                if self.caret >= self.len {
+                    return SessionUpdate::End;
+                }
+
+                self.caret += 1;
+                SessionUpdate::Continue
+            }
+            Key::Modifier | Key::Function(_) => SessionUpdate::Continue,
+            Key::Return | Key::Escape => SessionUpdate::End,
+            Key::Tab | Key::Other => match event.name.as_deref() {
+                Some(name)
+                    if !name.is_empty() && !name.chars().any(|c| c.is_control() && c != '\t') =>
+                {
+                    self.insert(name.graphemes(true).count());
+                    SessionUpdate::Continue
+                }
+                // Keys without text (like home or page up) move the caret
+                // somewhere unknown, and control characters are shortcuts.
+                _ => SessionUpdate::End,
+            },
+        }
+    }
+
+    fn insert(&mut self, count: usize) {
+        for stop in self.stops.iter_mut() {
+            if *stop >= self.caret {
+                *stop += count;
+            }
+        }
+
+        self.caret += count;
+        self.len += count;
+    }
+
+    fn remove(&mut self, count: usize) {
+        for stop in self.stops.iter_mut() {
+            if *stop >= self.caret {
+                *stop -= count;
+            }
+        }
+
+        self.caret -= count;
+        self.len -= count;
+    }
+
+    fn next_stop(&mut self, erase: usize) -> Option<CaretMove> {
+        if self.stops.is_empty() {
+            return None;
+        }
+
+        // Whatever the hotkey typed is removed again before moving.
+        self.remove(erase.min(self.caret));
+
+        let stop = self.stops.remove(0);
+        let caret_move = CaretMove {
+            erase,
+            left: self.caret.saturating_sub(stop),
+            right: stop.saturating_sub(self.caret),
+            output: self.output,
+        };
+
+        self.caret = stop;
+
+        Some(caret_move)
+    }
+}
@@ -0,0 +1,335 @@
+use serde::{Deserialize, Serialize};
+
+use uuid::Uuid;
+
+use crate::format::ExpansionFormat;
+use crate::input::Key;
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSettings {
+    pub trigger: TriggerSettings,
+    pub confirm: ConfirmSettings,
+    pub variables: VariableSettings,
+    pub expansions: Vec<Expansion>,
+    pub groups: Option<Vec<Group>>,
+    pub active_group: Option<String>,
+    #[serde(default)]
+    pub input: InputSettings,
+    #[serde(default)]
+    pub output: OutputSettings,
+    #[serde(default)]
+    pub snippets: SnippetSettings,
+    #[serde(default)]
+    pub undo: UndoSettings,
+    #[serde(default)]
+    pub history: HistorySettings,
+    #[serde(default)]
+    pub suggestions: SuggestionSettings,
+    #[serde(default)]
+    pub selection: SelectionSettings,
+    #[serde(default)]
+    pub shell: ShellSettings,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerSettings {
+    pub string: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmSettings {
+    pub chars: Vec<String>,
+    pub key_enter: bool,
+    pub key_right_arrow: bool,
+    pub append: bool,
+    pub auto: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VariableSettings {
+    pub separator: String,
+    /// Ask for the values of variables that were not typed after the
+    /// abbreviation in a popup before expanding.
+    #[serde(default)]
+    pub form: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InputSettings {
+    pub backend: InputBackend,
+    /// File with recorded key events, used by the replay backend.
+    pub replay_file: Option<String>,
+}
+
+/// Where key presses are read from.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum InputBackend {
+    /// Use evdev on Wayland sessions and rdev everywhere else.
+    #[default]
+    Auto,
+    Rdev,
+    /// Read `/dev/input` directly (Linux only).
+    Evdev,
+    /// Replay key events from `replay_file`.
+    Replay,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputSettings {
+    pub kind: OutputKind,
+    /// Paste expansions via the clipboard once their text has at least this
+    /// many characters, since typing long texts is slow.
+    #[serde(default)]
+    pub paste_threshold: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetSettings {
+    /// Key that moves the caret to the next tab stop of the last expansion.
+    pub next_stop_key: Key,
+}
+
+impl Default for SnippetSettings {
+    fn default() -> Self {
+        SnippetSettings {
+            next_stop_key: Key::Tab,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoSettings {
+    /// Undo an expansion by pressing backspace right after it.
+    pub backspace: bool,
+    /// Additional key that undoes the last expansion.
+    #[serde(default)]
+    pub key: Option<Key>,
+}
+
+impl Default for UndoSettings {
+    fn default() -> Self {
+        UndoSettings {
+            backspace: true,
+            key: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HistorySettings {
+    pub mode: HistoryMode,
+    /// Maximum number of remembered expansions.
+    pub limit: usize,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        HistorySettings {
+            mode: HistoryMode::Memory,
+            limit: 100,
+        }
+    }
+}
+
+/// Where performed expansions are remembered. Expansions can contain
+/// personal data, so they are only written to disk if asked for.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum HistoryMode {
+    /// Do not keep a history.
+    Off,
+    /// Keep the history until typls is closed.
+    #[default]
+    Memory,
+    /// Keep the history in a file next to the settings.
+    File,
+}
+
+/// Suggesting new expansions from phrases that are typed repeatedly.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestionSettings {
+    /// Typing is only analyzed if enabled.
+    pub enabled: bool,
+    /// How often a phrase has to be typed to be suggested.
+    pub min_count: u32,
+}
+
+impl Default for SuggestionSettings {
+    fn default() -> Self {
+        SuggestionSettings {
+            enabled: false,
+            min_count: 3,
+        }
+    }
+}
+
+/// Hotkeys that apply an expansion to the selected text.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionSettings {
+    pub hotkeys: Vec<SelectionHotkey>,
+}
+
+impl SelectionSettings {
+    /// The hotkey for a pressed key. Only function keys can be hotkeys, all
+    /// other keys are needed for typing, editing or shortcuts.
+    pub fn hotkey(&self, key: Key) -> Option<&SelectionHotkey> {
+        if !is_hotkey(key) {
+            return None;
+        }
+
+        self.hotkeys.iter().find(|hotkey| hotkey.key == key)
+    }
+
+    /// Hotkeys on keys that can not be hotkeys.
+    pub fn invalid_hotkeys(&self) -> impl Iterator<Item = &SelectionHotkey> {
+        self.hotkeys.iter().filter(|hotkey| !is_hotkey(hotkey.key))
+    }
+
+    /// Remove the hotkeys on keys that can not be hotkeys and return them.
+    pub fn remove_invalid_hotkeys(&mut self) -> Vec<SelectionHotkey> {
+        let (valid, invalid) = self
+            .hotkeys
+            .drain(..)
+            .partition(|hotkey| is_hotkey(hotkey.key));
+        self.hotkeys = valid;
+        invalid
+    }
+}
+
+fn is_hotkey(key: Key) -> bool {
+    matches!(key, Key::Function(_))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionHotkey {
+    pub key: Key,
+    /// Abbreviation and parameters, as they would be typed after the
+    /// trigger, like `fence|lang=rust`.
+    pub sequence: String,
+}
+
+/// Limits for `{shell:...}` variables. Which commands may run is not part of
+/// the settings, see [`crate::ShellPermissions`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellSettings {
+    /// Commands are stopped after this many milliseconds.
+    pub timeout: u64,
+    /// Maximum output of a command, in bytes.
+    pub max_output: usize,
+}
+
+impl Default for ShellSettings {
+    fn default() -> Self {
+        ShellSettings {
+            timeout: 1000,
+            max_output: 4096,
+        }
+    }
+}
+
+/// How expansions are inserted into the focused application.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputKind {
+    /// Simulate key presses with enigo.
+    #[default]
+    Enigo,
+    /// Type with a virtual uinput keyboard (Linux only), which also works on Wayland.
+    Uinput,
+    /// Put the text on the clipboard and paste it, then restore the previous
+    /// clipboard contents.
+    Clipboard,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Expansion {
+    #[serde(default = "generate_uuid")]
+    pub id: String,
+    pub abbr: String,
+    pub text: String,
+    pub group: Option<String>,
+    /// Overrides the output kind of the group and the global settings.
+    #[serde(default)]
+    pub output: Option<OutputKind>,
+    #[serde(default)]
+    pub format: ExpansionFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Group {
+    pub id: String,
+    pub name: String,
+    pub apps: Vec<App>,
+    /// Overrides the global output kind for expansions in this group.
+    #[serde(default)]
+    pub output: Option<OutputKind>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct App {
+    pub path: String,
+    pub os: String,
+}
+
+fn generate_uuid() -> String {
+    Uuid::new_v4().to_string()
+}
+
+pub const DEFAULT_CONFIRM_CHARS: [&str; 7] = [" ", ".", ";", "!", "?", ":", ","];
+
+pub fn default_settings() -> AppSettings {
+    AppSettings {
+        trigger: TriggerSettings {
+            string: "'".to_string(),
+        },
+        confirm: ConfirmSettings {
+            chars: DEFAULT_CONFIRM_CHARS
+                .iter()
+                .map(|&s| s.to_string())
+                .collect(),
+            key_enter: true,
+            key_right_arrow: true,
+            append: true,
+            auto: false,
+        },
+        variables: VariableSettings {
+            separator: "|".to_string(),
+            form: false,
+        },
+        expansions: vec![Expansion {
+            id: "typls".to_string(),
+            abbr: "typls".to_string(),
+            text: "Type less with typls: https://typls.app".to_string(),
+            group: None,
+            output: None,
+            format: ExpansionFormat::Plain,
+        }],
+        groups: Some(vec![]),
+        active_group: None,
+        input: InputSettings::default(),
+        output: OutputSettings::default(),
+        snippets: SnippetSettings::default(),
+        undo: UndoSettings::default(),
+        history: HistorySettings::default(),
+        suggestions: SuggestionSettings::default(),
+        selection: SelectionSettings::default(),
+        shell: ShellSettings::default(),
+    }
+}
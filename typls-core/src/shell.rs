@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings::ShellSettings;
+
+/// How often a running command is checked for having finished.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Commands the user allowed `{shell:...}` variables to run. They are kept
+/// apart from the settings and only changed after the user confirmed it, so
+/// editing the settings file alone can not run arbitrary commands.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellPermissions {
+    /// Commands every expansion may run.
+    pub allowed: Vec<String>,
+    /// Commands only the expansion with the id may run.
+    pub expansions: HashMap<String, Vec<String>>,
+}
+
+impl ShellPermissions {
+    pub fn new() -> ShellPermissions {
+        ShellPermissions::default()
+    }
+
+    /// Load permissions previously written with [`ShellPermissions::save`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<ShellPermissions, String> {
+        let contents = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+
+        serde_json::from_str(&contents)
+            .map_err(|error| format!("Invalid shell permissions: {}", error))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| error.to_string())?;
+
+        std::fs::write(path, json).map_err(|error| error.to_string())
+    }
+
+    /// Allow a command for all expansions, or only for the given one.
+    pub fn allow(&mut self, command: &str, expansion_id: Option<&str>) {
+        let commands = match expansion_id {
+            Some(id) => self.expansions.entry(id.to_string()).or_default(),
+            None => &mut self.allowed,
+        };
+
+        if !commands.iter().any(|allowed| allowed == command) {
+            commands.push(command.to_string());
+        }
+    }
+
+    /// Take back a permission given with [`ShellPermissions::allow`].
+    pub fn revoke(&mut self, command: &str, expansion_id: Option<&str>) {
+        match expansion_id {
+            Some(id) => {
+                if let Some(commands) = self.expansions.get_mut(id) {
+                    commands.retain(|allowed| allowed != command);
+
+                    if commands.is_empty() {
+                        self.expansions.remove(id);
+                    }
+                }
+            }
+            None => self.allowed.retain(|allowed| allowed != command),
+        }
+    }
+
+    /// Whether the expansion may run the command. Commands have to match
+    /// exactly.
+    pub fn is_allowed(&self, command: &str, expansion_id: &str) -> bool {
+        self.allowed.iter().any(|allowed| allowed == command)
+            || self
+                .expansions
+                .get(expansion_id)
+                .is_some_and(|commands| commands.iter().any(|allowed| allowed == command))
+    }
+}
+
+/// Run a command with the system shell and return what it printed, without
+/// the trailing line break. Fails if the command does not finish in time,
+/// prints too much or exits with an error.
+pub fn run_command(command: &str, settings: &ShellSettings) -> Result<String, String> {
+    #[cfg(target_os = "windows")]
+    let mut process = {
+        let mut process = Command::new("cmd");
+        process.args(["/C", command]);
+        process
+    };
+
+    #[cfg(not(target_os = "windows"))]
+    let mut process = {
+        let mut process = Command::new("sh");
+        process.args(["-c", command]);
+        process
+    };
+
+    let mut child = process
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| format!("Failed to run `{}`: {}", command, error))?;
+
+    // Read on another thread, a full pipe would otherwise block the command
+    // until it times out. One byte more than allowed tells if it was cut off.
+    let stdout = child.stdout.take().unwrap();
+    let limit = settings.max_output as u64 + 1;
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut output = vec![];
+        let result = stdout.take(limit).read_to_end(&mut output).map(|_| output);
+        let _ = sender.send(result);
+    });
+
+    let timeout = Duration::from_millis(settings.timeout);
+    let started = Instant::now();
+    let mut output = None;
+
+    let status = loop {
+        if output.is_none() {
+            output = receiver.try_recv().ok();
+        }
+
+        let too_long = matches!(&output, Some(Ok(output)) if output.len() > settings.max_output);
+        let timed_out = started.elapsed() >= timeout;
+
+        if too_long || timed_out {
+            let _ = child.kill();
+            let _ = child.wait();
+
+            return Err(if too_long {
+                format!(
+                    "`{}` printed more than {} bytes",
+                    command, settings.max_output
+                )
+            } else {
+                format!(
+                    "`{}` did not finish within {} ms",
+                    command, settings.timeout
+                )
+            });
+        }
+
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(error) => return Err(format!("Failed to run `{}`: {}", command, error)),
+        }
+    };
+
+    if !status.success() {
+        return Err(format!("`{}` failed with {}", command, status));
+    }
+
+    // Processes started in the background can keep the output open after
+    // the command finished.
+    let output = match output {
+        Some(output) => output,
+        None => receiver
+            .recv_timeout(timeout.saturating_sub(started.elapsed()))
+            .map_err(|_| {
+                format!(
+                    "`{}` did not finish within {} ms",
+                    command, settings.timeout
+                )
+            })?,
+    }
+    .map_err(|error| format!("Failed to read the output of `{}`: {}", command, error))?;
+
+    if output.len() > settings.max_output {
+        return Err(format!(
+            "`{}` printed more than {} bytes",
+            command, settings.max_output
+        ));
+    }
+
+    let output = String::from_utf8(output)
+        .map_err(|_| format!("`{}` printed something that is not text", command))?;
+
+    Ok(output.trim_end_matches(['\n', '\r']).to_string())
+}
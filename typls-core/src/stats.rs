@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::capture::CaptureSignal;
+use crate::history::unix_timestamp;
+use crate::plan::{grapheme_count, ExpansionPlan};
+use crate::settings::{AppSettings, Expansion};
+
+/// Usage counters of a single expansion.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpansionStats {
+    pub count: u64,
+    /// Seconds since the Unix epoch.
+    pub last_used: u64,
+    /// Characters typed by the user, including the trigger and parameters.
+    pub chars_typed: u64,
+    /// Characters typed by typls.
+    pub chars_produced: u64,
+    /// Number of uses per application (process path).
+    pub apps: HashMap<String, u64>,
+}
+
+/// Usage counters of all expansions, by expansion id.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageStats {
+    expansions: HashMap<String, ExpansionStats>,
+}
+
+/// Statistics of an expansion together with the expansion it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpansionUsage {
+    pub expansion_id: String,
+    pub abbr: String,
+    #[serde(flatten)]
+    pub stats: ExpansionStats,
+}
+
+/// Aggregated statistics over all expansions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsSummary {
+    pub count: u64,
+    pub chars_typed: u64,
+    pub chars_produced: u64,
+    /// Characters the user did not have to type. Negative if the
+    /// abbreviations are longer than their texts.
+    pub chars_saved: i64,
+    /// Number of uses per application over all expansions.
+    pub apps: HashMap<String, u64>,
+    /// Used expansions that still exist, most used first.
+    pub expansions: Vec<ExpansionUsage>,
+}
+
+impl UsageStats {
+    pub fn new() -> UsageStats {
+        UsageStats::default()
+    }
+
+    /// Load statistics previously written with [`UsageStats::save`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<UsageStats, String> {
+        let contents = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+
+        serde_json::from_str(&contents).map_err(|error| format!("Invalid stats: {}", error))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|error| error.to_string())?;
+
+        std::fs::write(path, json).map_err(|error| error.to_string())
+    }
+
+    /// Count a performed expansion.
+    pub fn record(&mut self, signal: &CaptureSignal, plan: &ExpansionPlan, app: &str) {
+        let enter = if signal.append_enter { 1 } else { 0 };
+
+        let stats = self
+            .expansions
+            .entry(plan.expansion_id.clone())
+            .or_default();
+
+        stats.count += 1;
+        stats.last_used = unix_timestamp();
+        stats.chars_typed += plan.erase as u64;
+        stats.chars_produced += (grapheme_count(&plan.text) + enter) as u64;
+        *stats.apps.entry(app.to_string()).or_default() += 1;
+    }
+
+    pub fn get(&self, expansion_id: &str) -> Option<&ExpansionStats> {
+        self.expansions.get(expansion_id)
+    }
+
+    pub fn clear(&mut self) {
+        self.expansions.clear();
+    }
+
+    /// Aggregate the statistics of all expansions that still exist.
+    pub fn summary(&self, settings: &AppSettings) -> StatsSummary {
+        let mut expansions = settings
+            .expansions
+            .iter()
+            .filter_map(|expansion| {
+                let stats = self.get(&expansion.id)?;
+
+                Some(ExpansionUsage {
+                    expansion_id: expansion.id.clone(),
+                    abbr: expansion.abbr.clone(),
+                    stats: stats.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        expansions.sort_by_key(|usage| std::cmp::Reverse(usage.stats.count));
+
+        let mut apps = HashMap::new();
+        for usage in expansions.iter() {
+            for (app, count) in usage.stats.apps.iter() {
+                *apps.entry(app.clone()).or_default() += count;
+            }
+        }
+
+        let count = expansions.iter().map(|usage| usage.stats.count).sum();
+        let chars_typed = expansions.iter().map(|usage| usage.stats.chars_typed).sum();
+        let chars_produced = expansions
+            .iter()
+            .map(|usage| usage.stats.chars_produced)
+            .sum();
+
+        StatsSummary {
+            count,
+            chars_typed,
+            chars_produced,
+            chars_saved: chars_produced as i64 - chars_typed as i64,
+            apps,
+            expansions,
+        }
+    }
+
+    /// Expansions that were never used.
+    pub fn unused<'a>(&self, settings: &'a AppSettings) -> Vec<&'a Expansion> {
+        settings
+            .expansions
+            .iter()
+            .filter(|expansion| self.get(&expansion.id).is_none_or(|stats| stats.count == 0))
+            .collect()
+    }
+}
@@ -0,0 +1,84 @@
+use chrono::NaiveDate;
+
+/// The kind of value a variable takes, like `int` in `{count:int}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VariableType {
+    /// A whole number, like `42` or `-3`.
+    Int,
+    /// A date like `2024-05-17`.
+    Date,
+    Email,
+    /// Values matching the regular expression as a whole, like
+    /// `[A-Z]{3}-\d+` in `{id:/[A-Z]{3}-\d+/}`.
+    Pattern(String),
+}
+
+impl VariableType {
+    /// The type with the given name. Patterns are created with
+    /// [`VariableType::pattern`].
+    pub fn parse(name: &str) -> Option<VariableType> {
+        match name {
+            "int" => Some(VariableType::Int),
+            "date" => Some(VariableType::Date),
+            "email" => Some(VariableType::Email),
+            _ => None,
+        }
+    }
+
+    /// A pattern type. Fails if the regular expression is invalid.
+    pub fn pattern(pattern: &str) -> Result<VariableType, String> {
+        pattern_regex(pattern)?;
+
+        Ok(VariableType::Pattern(pattern.to_string()))
+    }
+
+    /// Check that the value of the variable `name` has this type.
+    pub fn validate(&self, name: &str, value: &str) -> Result<(), String> {
+        let valid = match self {
+            VariableType::Int => value.parse::<i64>().is_ok(),
+            VariableType::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+            VariableType::Email => is_email(value),
+            VariableType::Pattern(pattern) => pattern_regex(pattern)?.is_match(value),
+        };
+
+        match (valid, value.is_empty()) {
+            (true, _) => Ok(()),
+            (false, true) => Err(format!("`{}` needs {}", name, self.description())),
+            (false, false) => Err(format!(
+                "`{}` needs {}, got `{}`",
+                name,
+                self.description(),
+                value
+            )),
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            VariableType::Int => "a whole number".to_string(),
+            VariableType::Date => "a date like 2024-05-17".to_string(),
+            VariableType::Email => "an email address".to_string(),
+            VariableType::Pattern(pattern) => format!("a value matching `/{}/`", pattern),
+        }
+    }
+}
+
+/// The regular expression of a pattern, matching whole values only.
+fn pattern_regex(pattern: &str) -> Result<regex::Regex, String> {
+    regex::Regex::new(&format!("^(?:{})$", pattern))
+        .map_err(|error| format!("Invalid pattern `/{}/`: {}", pattern, error))
+}
+
+/// A loose check for `name@example.com`, without spaces and with a dot in
+/// the domain.
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+
+    !local.is_empty()
+        && !value.contains(char::is_whitespace)
+        && !domain.contains('@')
+        && domain.split('.').count() > 1
+        && domain.split('.').all(|label| !label.is_empty())
+}
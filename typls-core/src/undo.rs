@@ -0,0 +1,118 @@
+use serde::Serialize;
+
+use crate::capture::CaptureSignal;
+use crate::input::{Key, KeyEvent};
+use crate::plan::{grapheme_count, ExpansionPlan, PlanKey};
+use crate::settings::{OutputKind, UndoSettings};
+
+/// Reverts an expansion: the typed text is erased and the abbreviation typed
+/// again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoPlan {
+    /// Presses of the right arrow key to get the caret back to the end of the
+    /// expansion, if it was placed somewhere inside.
+    pub right: usize,
+    pub erase: usize,
+    /// The trigger and the captured sequence.
+    pub text: String,
+    pub output: OutputKind,
+    /// The window the expansion was typed into.
+    pub window: String,
+}
+
+/// The last expansion, kept until the next key press decides whether it
+/// should be undone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastExpansion {
+    right: usize,
+    produced: usize,
+    text: String,
+    output: OutputKind,
+    window: String,
+}
+
+impl LastExpansion {
+    /// Returns `None` for formatted expansions. They are pasted as rich
+    /// text, so there is no telling how many characters the app shows.
+    pub fn new(
+        signal: &CaptureSignal,
+        plan: &ExpansionPlan,
+        trigger: &str,
+        window: &str,
+    ) -> Option<LastExpansion> {
+        if plan.rich.is_some() {
+            return None;
+        }
+
+        Some(LastExpansion {
+            right: plan
+                .keys
+                .iter()
+                .filter(|&&key| key == PlanKey::Left)
+                .count(),
+            produced: grapheme_count(&plan.text) + if signal.append_enter { 1 } else { 0 },
+            text: format!("{}{}", trigger, signal.sequence),
+            output: plan.output,
+            window: window.to_string(),
+        })
+    }
+
+    /// Undo the expansion. `already_erased` characters were removed by the
+    /// key that requested the undo.
+    fn undo(self, already_erased: usize) -> UndoPlan {
+        UndoPlan {
+            right: self.right,
+            erase: self.produced.saturating_sub(already_erased),
+            text: self.text,
+            output: self.output,
+            window: self.window,
+        }
+    }
+}
+
+/// The time right after an expansion, in which it can be undone. Any key
+/// press other than the undo keys closes it.
+#[derive(Debug, Default)]
+pub struct UndoWindow {
+    last: Option<LastExpansion>,
+}
+
+impl UndoWindow {
+    pub fn new() -> UndoWindow {
+        UndoWindow::default()
+    }
+
+    pub fn open(&mut self, last: LastExpansion) {
+        self.last = Some(last);
+    }
+
+    pub fn close(&mut self) {
+        self.last = None;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// Feed a key press. Returns what to do if it undoes the last expansion.
+    /// Modifier keys are ignored, so they can be part of the hotkey.
+    pub fn handle(&mut self, event: &KeyEvent, settings: &UndoSettings) -> Option<UndoPlan> {
+        if event.key == Key::Modifier {
+            return None;
+        }
+
+        let last = self.last.take()?;
+
+        if settings.key == Some(event.key) {
+            return Some(last.undo(0));
+        }
+
+        // The focused app already removed one character for the backspace.
+        if settings.backspace && event.key == Key::Backspace {
+            return Some(last.undo(1));
+        }
+
+        None
+    }
+}
@@ -0,0 +1,651 @@
+use std::collections::HashMap;
+
+use crate::context::is_builtin;
+use crate::filters::Filter;
+use crate::settings::Expansion;
+use crate::types::VariableType;
+
+/// A variable as written in the text, like `{name|upper=Peter}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    /// The name including the arguments of built-in variables, like
+    /// `date:%A`. Empty for unnamed variables (`{}`).
+    pub name: String,
+    pub default: String,
+    /// Applied in order to the value, after the default was filled in.
+    pub filters: Vec<Filter>,
+    /// The options of a choice variable, like `formal` and `casual` for
+    /// `{tone:formal|casual}`. Empty for other variables.
+    pub choices: Vec<String>,
+    /// The type its values must have, like `int` in `{count:int}`.
+    pub kind: Option<VariableType>,
+}
+
+impl Variable {
+    /// Check a value passed for this variable. Choices pick one of their
+    /// options, typed variables only take values of their type.
+    pub fn accept(&self, value: &str) -> Result<String, String> {
+        if let Some(kind) = &self.kind {
+            kind.validate(&self.name, value)?;
+        }
+
+        self.choose(value)
+    }
+
+    /// Pick an option of a choice variable by its name, its number (starting
+    /// at 1) or the start of its name. Other variables take any value.
+    pub fn choose(&self, value: &str) -> Result<String, String> {
+        if self.choices.is_empty() {
+            return Ok(value.to_string());
+        }
+
+        let lowercase = value.to_lowercase();
+
+        if let Some(choice) = self
+            .choices
+            .iter()
+            .find(|choice| choice.to_lowercase() == lowercase)
+        {
+            return Ok(choice.clone());
+        }
+
+        if let Some(choice) = value
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| self.choices.get(number.checked_sub(1)?))
+        {
+            return Ok(choice.clone());
+        }
+
+        let matching = self
+            .choices
+            .iter()
+            .filter(|choice| !value.is_empty() && choice.to_lowercase().starts_with(&lowercase))
+            .collect::<Vec<_>>();
+
+        match matching.as_slice() {
+            [choice] => Ok(choice.to_string()),
+            _ => Err(format!(
+                "`{}` is not one of the options of `{}`: {}",
+                value,
+                self.name,
+                self.choices.join(", ")
+            )),
+        }
+    }
+}
+
+/// The test of a conditional section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    /// The variable that is tested, like `company`.
+    pub name: String,
+    /// `None` tests whether the variable has a value that is not empty
+    /// (`{?company}`), otherwise whether it equals the value
+    /// (`{?tone=formal}`, or `{?company=}` for empty).
+    pub value: Option<String>,
+}
+
+impl Condition {
+    /// Whether the value of the variable passes the test.
+    pub fn matches(&self, value: &str) -> bool {
+        match &self.value {
+            Some(expected) => value == expected,
+            None => !value.is_empty(),
+        }
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{{?{}={}}}", self.name, value),
+            None => write!(f, "{{?{}}}", self.name),
+        }
+    }
+}
+
+/// A piece of an expansion text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Text(String),
+    Variable(Variable),
+    /// A cursor marker or tab stop, like `{|}` or `{$1}`. Their positions
+    /// are tracked while rendering and handled when planning.
+    Marker(String),
+    /// Start of a conditional section, like `{?company}`.
+    If(Condition),
+    /// `{:else}`, starts the part shown if the condition is not met.
+    Else,
+    /// End of a conditional section, like `{/company}`.
+    EndIf(String),
+}
+
+/// An expansion text with its conditional sections resolved into a tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Text(String),
+    Variable(Variable),
+    Marker(String),
+    Section {
+        condition: Condition,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+#[derive(Debug)]
+pub struct ExpansionVariables {
+    pub named: HashMap<String, Variable>,
+    pub unnamed: Vec<Variable>,
+    /// Whether the text contains a cursor marker (`{|}`).
+    pub has_cursor: bool,
+    /// Numbers of the tab stops (`{$1}`, `{$2}`, ...), in order of appearance.
+    pub tab_stops: Vec<usize>,
+}
+
+/// Marks where the cursor should be placed after the expansion was typed.
+pub const CURSOR_MARKER: &str = "{|}";
+
+/// Split an expansion text into literal text, variables and markers.
+///
+/// A variable is `{name:args|filter|filter:arg=default}`, where every part
+/// but the braces is optional. Conditional sections are
+/// `{?name}...{:else}...{/name}`. Shell variables take everything up to the
+/// closing brace as the command. Braces that do not contain either, like in
+/// `if (a) { b(); }`, are literal text. Variables with unknown or invalid
+/// filters are an error.
+///
+/// A backslash in front of a variable or section keeps it literal: `\{name}`
+/// is typed as `{name}`, while `\\{name}` is a backslash and the variable.
+/// Backslashes in front of other braces are typed as they are.
+pub fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut literal = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        literal.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = pattern_variable_end(rest)
+            .or_else(|| shell_variable_end(rest))
+            .or_else(|| rest.find('}'))
+        else {
+            break;
+        };
+
+        let raw = &rest[..=end];
+        let token = parse_token(raw).map_err(|error| format!("{} in `{}`", error, raw));
+
+        // Every pair of backslashes in front of the brace is one backslash,
+        // an odd one escapes the brace.
+        let backslashes = literal.len() - literal.trim_end_matches('\\').len();
+
+        let token = match token {
+            Ok(None) => None,
+            _ if backslashes % 2 == 1 => {
+                literal.truncate(literal.len() - backslashes.div_ceil(2));
+                literal.push_str(raw);
+                rest = &rest[end + 1..];
+                continue;
+            }
+            token => {
+                literal.truncate(literal.len() - backslashes / 2);
+                token?
+            }
+        };
+
+        match token {
+            Some(token) => {
+                if !literal.is_empty() {
+                    tokens.push(Token::Text(std::mem::take(&mut literal)));
+                }
+
+                tokens.push(token);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                // Not a variable, but it could start right after this brace.
+                literal.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        tokens.push(Token::Text(literal));
+    }
+
+    Ok(tokens)
+}
+
+/// Resolve the conditional sections of a text into a tree. Sections have to
+/// be closed in the reverse order they were opened.
+pub fn parse_template(text: &str) -> Result<Vec<Node>, String> {
+    struct Frame {
+        condition: Option<Condition>,
+        then: Vec<Node>,
+        otherwise: Option<Vec<Node>>,
+    }
+
+    impl Frame {
+        fn push(&mut self, node: Node) {
+            self.otherwise.as_mut().unwrap_or(&mut self.then).push(node);
+        }
+    }
+
+    let mut stack = vec![Frame {
+        condition: None,
+        then: vec![],
+        otherwise: None,
+    }];
+
+    for token in tokenize(text)? {
+        let frame = stack.last_mut().unwrap();
+
+        match token {
+            Token::Text(text) => frame.push(Node::Text(text)),
+            Token::Variable(variable) => frame.push(Node::Variable(variable)),
+            Token::Marker(marker) => frame.push(Node::Marker(marker)),
+            Token::If(condition) => stack.push(Frame {
+                condition: Some(condition),
+                then: vec![],
+                otherwise: None,
+            }),
+            Token::Else => match &frame.condition {
+                None => return Err("`{:else}` outside of a section".to_string()),
+                Some(condition) if frame.otherwise.is_some() => {
+                    return Err(format!("Second `{{:else}}` in `{}`", condition))
+                }
+                Some(_) => frame.otherwise = Some(vec![]),
+            },
+            Token::EndIf(name) => {
+                match &frame.condition {
+                    None => return Err(format!("`{{/{0}}}` without `{{?{0}}}`", name)),
+                    Some(condition) if condition.name != name => {
+                        return Err(format!(
+                            "`{{/{}}}` closes `{}`, close it with `{{/{}}}` first",
+                            name, condition, condition.name
+                        ))
+                    }
+                    Some(_) => (),
+                }
+
+                let frame = stack.pop().unwrap();
+                stack.last_mut().unwrap().push(Node::Section {
+                    condition: frame.condition.unwrap(),
+                    then: frame.then,
+                    otherwise: frame.otherwise.unwrap_or_default(),
+                });
+            }
+        }
+    }
+
+    let frame = stack.pop().unwrap();
+    if let Some(condition) = frame.condition {
+        return Err(format!(
+            "`{}` is never closed, add `{{/{}}}`",
+            condition, condition.name
+        ));
+    }
+
+    Ok(frame.then)
+}
+
+/// Parse a variable, section tag or marker including its braces. Returns
+/// `None` for anything else.
+fn parse_token(raw: &str) -> Result<Option<Token>, String> {
+    let content = &raw[1..raw.len() - 1];
+
+    let token = if content == "|" || is_tab_stop(content) {
+        Some(Token::Marker(raw.to_string()))
+    } else if content == ":else" {
+        Some(Token::Else)
+    } else if let Some(condition) = content.strip_prefix('?') {
+        let (name, value) = match condition.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (condition, None),
+        };
+
+        is_variable_name(name).then(|| {
+            Token::If(Condition {
+                name: name.to_string(),
+                value,
+            })
+        })
+    } else if let Some(name) = content.strip_prefix('/') {
+        is_variable_name(name).then(|| Token::EndIf(name.to_string()))
+    } else {
+        parse_variable(content)?.map(Token::Variable)
+    };
+
+    Ok(token)
+}
+
+fn is_tab_stop(content: &str) -> bool {
+    content
+        .strip_prefix('$')
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Split a variable with a pattern type, like `id:/[A-Z]{3}/=ABC`, into its
+/// name, the pattern and what follows the pattern. The pattern ends at the
+/// first `/` that is not escaped with `\`.
+fn split_pattern(content: &str) -> Option<(&str, &str, &str)> {
+    let (name, rest) = content.split_once(":/")?;
+
+    if name.is_empty() || !name.chars().all(is_name_char) || is_builtin(name) {
+        return None;
+    }
+
+    let mut escaped = false;
+    let end = rest.char_indices().find_map(|(index, c)| {
+        let end = c == '/' && !escaped;
+        escaped = c == '\\' && !escaped;
+        end.then_some(index)
+    })?;
+
+    Some((name, &rest[..end], &rest[end + 1..]))
+}
+
+/// The index of the closing brace of a variable with a pattern type at the
+/// start of the text. Patterns may contain braces, like `{3}`.
+fn pattern_variable_end(text: &str) -> Option<usize> {
+    let (name, pattern, rest) = split_pattern(&text[1..])?;
+
+    if !rest.starts_with(['}', '=']) {
+        return None;
+    }
+
+    Some(name.len() + pattern.len() + 4 + rest.find('}')?)
+}
+
+/// Start of a shell variable, like `{shell:git branch --show-current}`.
+const SHELL_PREFIX: &str = "{shell:";
+
+/// The index of the closing brace of a shell variable at the start of the
+/// text. Commands may contain braces escaped with `\`, like `\}`.
+fn shell_variable_end(text: &str) -> Option<usize> {
+    let command = text.strip_prefix(SHELL_PREFIX)?;
+
+    let mut escaped = false;
+    let end = command.char_indices().find_map(|(index, c)| {
+        let end = c == '}' && !escaped;
+        escaped = c == '\\' && !escaped;
+        end.then_some(index)
+    })?;
+
+    Some(SHELL_PREFIX.len() + end)
+}
+
+/// A shell variable. The whole content is the command, so it can contain
+/// `|` and `=`, and `\}` stands for a closing brace.
+fn shell_variable(command: &str) -> Variable {
+    let mut unescaped = String::new();
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        unescaped.push(c);
+
+        match chars.peek() {
+            Some('}') if c == '\\' => {
+                unescaped.pop();
+                unescaped.push('}');
+                chars.next();
+            }
+            // Other backslashes are passed to the shell as they are.
+            Some('\\') if c == '\\' => {
+                unescaped.push('\\');
+                chars.next();
+            }
+            _ => (),
+        }
+    }
+
+    Variable {
+        name: format!("shell:{}", unescaped),
+        default: String::new(),
+        filters: vec![],
+        choices: vec![],
+        kind: None,
+    }
+}
+
+/// Parse the content between the braces. Returns `None` if it does not look
+/// like a variable at all.
+fn parse_variable(content: &str) -> Result<Option<Variable>, String> {
+    if let Some(command) = content.strip_prefix(&SHELL_PREFIX[1..]) {
+        return Ok(Some(shell_variable(command)));
+    }
+
+    // Typed variables with a pattern, like `{id:/[A-Z]{3}-\d+/=ABC-1}`.
+    if let Some((name, pattern, rest)) = split_pattern(content) {
+        let default = match rest {
+            "" => Some(""),
+            rest => rest.strip_prefix('='),
+        };
+
+        if let Some(default) = default {
+            return typed_variable(name, VariableType::pattern(pattern)?, default);
+        }
+    }
+
+    let (head, default) = content.split_once('=').unwrap_or((content, ""));
+
+    let base_end = head.find([':', '|']).unwrap_or(head.len());
+    let base = &head[..base_end];
+
+    // Other variables than the built-in ones with arguments are choices,
+    // like `{tone:formal|casual}`. The first option is the default.
+    if head[base_end..].starts_with(':') && !is_builtin(base) {
+        let choices = head[base_end + 1..]
+            .split('|')
+            .map(|choice| choice.to_string())
+            .collect::<Vec<_>>();
+
+        // Anything else with spaces around the options is most likely code.
+        let is_choice = |choice: &String| {
+            !choice.is_empty() && choice.trim() == choice && !choice.contains(['{', '"'])
+        };
+
+        if base.is_empty() || !base.chars().all(is_name_char) || !choices.iter().all(is_choice) {
+            return Ok(None);
+        }
+
+        // A single option naming a type is a typed variable, like `{count:int}`.
+        if let [choice] = choices.as_slice() {
+            if let Some(kind) = VariableType::parse(choice) {
+                return typed_variable(base, kind, default);
+            }
+        }
+
+        // Spaces are only allowed if there is more than one option,
+        // `{a:1, b:2}` is code.
+        if choices.len() == 1 && choices[0].contains(char::is_whitespace) {
+            return Ok(None);
+        }
+
+        // Options share the `|` with filters, so `{tone:formal|upper}` could
+        // be either. Filters do not work on choices, so it is neither.
+        if let Some(filter) = choices[1..]
+            .iter()
+            .find(|choice| Filter::parse(choice).is_ok())
+        {
+            return Err(format!(
+                "`{}` is a filter and can not be an option of `{}`, filters do not work on choices",
+                filter, base
+            ));
+        }
+
+        let mut variable = Variable {
+            name: base.to_string(),
+            default: choices[0].clone(),
+            filters: vec![],
+            choices,
+            kind: None,
+        };
+
+        if !default.is_empty() {
+            variable.default = variable.choose(default)?;
+        }
+
+        return Ok(Some(variable));
+    }
+
+    let mut parts = head.split('|');
+    let name = parts.next().unwrap_or_default();
+    let filters = parts.collect::<Vec<_>>();
+
+    if !name.is_empty() && !is_variable_name(name) {
+        return Ok(None);
+    }
+
+    let is_filter_name = |filter: &&str| {
+        let name = filter.split(':').next().unwrap_or_default();
+        !name.is_empty() && name.chars().all(is_name_char)
+    };
+
+    if !filters.iter().all(is_filter_name) {
+        return Ok(None);
+    }
+
+    let filters = filters
+        .into_iter()
+        .map(Filter::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Some(Variable {
+        name: name.to_string(),
+        default: default.to_string(),
+        filters,
+        choices: vec![],
+        kind: None,
+    }))
+}
+
+/// A variable whose values have to be of the given type. A default has to be
+/// of that type as well.
+fn typed_variable(
+    name: &str,
+    kind: VariableType,
+    default: &str,
+) -> Result<Option<Variable>, String> {
+    if !default.is_empty() {
+        kind.validate(name, default)?;
+    }
+
+    Ok(Some(Variable {
+        name: name.to_string(),
+        default: default.to_string(),
+        filters: vec![],
+        choices: vec![],
+        kind: Some(kind),
+    }))
+}
+
+/// Whether the text is a name of a variable, with the arguments of built-in
+/// variables, like `name` or `date:%d %B`.
+fn is_variable_name(name: &str) -> bool {
+    let (base, args) = name.split_once(':').unwrap_or((name, ""));
+
+    // Formats of built-in variables may contain spaces, like
+    // `{date:%d %B}`. Anything else with spaces is most likely code.
+    !base.is_empty()
+        && base.chars().all(is_name_char)
+        && (!args.contains(char::is_whitespace) || is_builtin(base))
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+/// Collect the variables of an expansion, including the ones only tested by
+/// conditional sections. Named variables are listed once, with the default
+/// of their first occurrence.
+pub fn parse_variables(expansion: &Expansion) -> Result<ExpansionVariables, String> {
+    Ok(variables_of(&parse_template(&expansion.text)?))
+}
+
+/// The variables of an already parsed text.
+pub(crate) fn variables_of(nodes: &[Node]) -> ExpansionVariables {
+    let mut variables = ExpansionVariables {
+        named: HashMap::new(),
+        unnamed: Vec::new(),
+        has_cursor: false,
+        tab_stops: Vec::new(),
+    };
+
+    let mut conditions = vec![];
+    collect_variables(nodes, &mut variables, &mut conditions);
+
+    // Variables that are only tested are listed too, but the default of a
+    // variable comes from where it is inserted.
+    for name in conditions {
+        variables
+            .named
+            .entry(name.clone())
+            .or_insert_with(|| Variable {
+                name,
+                default: String::new(),
+                filters: vec![],
+                choices: vec![],
+                kind: None,
+            });
+    }
+
+    variables
+}
+
+fn collect_variables(
+    nodes: &[Node],
+    variables: &mut ExpansionVariables,
+    conditions: &mut Vec<String>,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => (),
+            Node::Marker(marker) if marker == CURSOR_MARKER => variables.has_cursor = true,
+            Node::Marker(marker) => {
+                if let Ok(number) = marker[2..marker.len() - 1].parse() {
+                    variables.tab_stops.push(number);
+                }
+            }
+            Node::Variable(variable) if variable.name.is_empty() => {
+                variables.unnamed.push(variable.clone())
+            }
+            Node::Variable(variable) => {
+                variables
+                    .named
+                    .entry(variable.name.clone())
+                    .or_insert_with(|| variable.clone());
+            }
+            Node::Section {
+                condition,
+                then,
+                otherwise,
+            } => {
+                conditions.push(condition.name.clone());
+                collect_variables(then, variables, conditions);
+                collect_variables(otherwise, variables, conditions);
+            }
+        }
+    }
+}
+
+/// Whether the text contains variables that need parameters. Used to skip
+/// auto confirmation for such expansions. Cursor markers, tab stops and
+/// built-in variables do not need parameters. Texts with invalid variables
+/// are treated as having variables.
+pub fn has_variables(text: &str) -> bool {
+    let Ok(tokens) = tokenize(text) else {
+        return true;
+    };
+
+    tokens.iter().any(|token| match token {
+        Token::Variable(variable) => variable.name.is_empty() || !is_builtin(&variable.name),
+        Token::If(condition) => !is_builtin(&condition.name),
+        _ => false,
+    })
+}
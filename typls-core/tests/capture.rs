@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex};
+
+use typls_core::{
+    default_settings, AppSettings, Capture, CaptureSignal, InputSource, Key, KeyEvent, ReplaySource,
+};
+
+mod common;
+
+use common::settings_with_all;
+
+fn replay(mut source: ReplaySource, settings: AppSettings) -> Vec<CaptureSignal> {
+    let signals = Arc::new(Mutex::new(vec![]));
+    let signals_ = Arc::clone(&signals);
+    let mut capture = Capture::new();
+
+    source
+        .listen(Box::new(move |event| {
+            if let Some(signal) = capture.handle(&event, &settings) {
+                signals_.lock().unwrap().push(signal);
+            }
+        }))
+        .unwrap();
+
+    let signals = signals.lock().unwrap();
+    signals.clone()
+}
+
+fn typed(text: &str) -> Vec<KeyEvent> {
+    text.chars()
+        .map(|c| KeyEvent::text(&c.to_string()))
+        .collect()
+}
+
+#[test]
+fn replays_recorded_file() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/hi_peter.jsonl");
+    let source = ReplaySource::from_file(path).unwrap();
+
+    let signals = replay(source, default_settings());
+
+    assert_eq!(
+        signals,
+        vec![CaptureSignal {
+            sequence: "hi|Peter".to_string(),
+            append: " ".to_string(),
+            append_enter: false,
+        }]
+    );
+}
+
+#[test]
+fn confirms_with_enter_and_cancels_with_escape() {
+    let mut events = typed("'ab");
+    events.push(KeyEvent::key(Key::Escape));
+    events.extend(typed("'cd"));
+    events.push(KeyEvent::key(Key::Return));
+
+    let signals = replay(ReplaySource::new(events), default_settings());
+
+    assert_eq!(
+        signals,
+        vec![CaptureSignal {
+            sequence: "cd".to_string(),
+            append: "".to_string(),
+            append_enter: true,
+        }]
+    );
+}
+
+#[test]
+fn auto_confirms_unambiguous_abbreviation() {
+    let mut settings = settings_with_all(&[("typlsx", "x"), ("sig", "Best regards")]);
+    settings.confirm.auto = true;
+
+    let signals = replay(ReplaySource::new(typed("'sig 'typls ")), settings);
+
+    assert_eq!(
+        signals,
+        vec![
+            CaptureSignal {
+                sequence: "sig".to_string(),
+                append: "".to_string(),
+                append_enter: false,
+            },
+            CaptureSignal {
+                sequence: "typls".to_string(),
+                append: " ".to_string(),
+                append_enter: false,
+            },
+        ]
+    );
+}
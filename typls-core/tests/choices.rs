@@ -0,0 +1,96 @@
+use typls_core::{expand, parse_variables, Expansion, ExpansionContext};
+
+mod common;
+
+use common::expand_with_errors;
+
+const REPLY: &str =
+    "{tone:formal|casual|friendly}: {?tone=formal}Dear {name}{:else}Hi {name}{/tone}";
+
+#[test]
+fn first_option_is_default() {
+    let (text, _) = expand_with_errors(REPLY, "mail|name=Anna");
+
+    assert_eq!(text.unwrap(), "formal: Dear Anna");
+}
+
+#[test]
+fn selects_option_by_name_index_or_prefix() {
+    for tone in ["casual", "Casual", "2", "c", "ca"] {
+        let (text, _) = expand_with_errors(REPLY, &format!("mail|tone={}|name=Anna", tone));
+        assert_eq!(text.unwrap(), "casual: Hi Anna", "{}", tone);
+    }
+
+    let (text, _) = expand_with_errors(REPLY, "mail|tone=fo|name=Anna");
+    assert_eq!(text.unwrap(), "formal: Dear Anna");
+}
+
+#[test]
+fn rejects_unknown_or_ambiguous_options() {
+    for tone in ["rude", "f", "4", "0"] {
+        let (text, errors) = expand_with_errors(REPLY, &format!("mail|tone={}", tone));
+
+        assert_eq!(text, None, "{}", tone);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("formal, casual, friendly"));
+    }
+}
+
+#[test]
+fn allows_spaces_in_options_and_explicit_default() {
+    let text = "{closing:Best regards|Cheers=cheers}, {name=Anna}";
+
+    assert_eq!(expand_with_errors(text, "mail").0.unwrap(), "Cheers, Anna");
+    assert_eq!(
+        expand_with_errors(text, "mail|closing=b").0.unwrap(),
+        "Best regards, Anna"
+    );
+}
+
+#[test]
+fn does_not_depend_on_separator() {
+    let mut settings = common::settings_with("mail", REPLY);
+    settings.variables.separator = ",".to_string();
+
+    let text = expand(
+        "mail,tone=3,name=Anna",
+        &settings,
+        &ExpansionContext::default(),
+    );
+
+    assert_eq!(text.unwrap(), "friendly: Hi Anna");
+}
+
+#[test]
+fn lists_options() {
+    let variables = parse_variables(&Expansion {
+        text: REPLY.to_string(),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let tone = &variables.named["tone"];
+    assert_eq!(tone.choices, vec!["formal", "casual", "friendly"]);
+    assert_eq!(tone.default, "formal");
+}
+
+#[test]
+fn keeps_code_literal() {
+    for text in ["{a:1, b:2}", "{a: 1}", "{a:b || c}"] {
+        assert_eq!(expand_with_errors(text, "mail").0.unwrap(), text);
+    }
+}
+
+#[test]
+fn rejects_filters_on_choices() {
+    let (text, errors) = expand_with_errors("{tone:formal|casual|upper}", "mail|tone=c");
+
+    assert_eq!(text, None);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("`upper` is a filter"));
+
+    // The first option is not mistaken for a filter.
+    let (text, errors) = expand_with_errors("{case:upper|as typed}", "mail|case=a");
+    assert_eq!(text.unwrap(), "as typed");
+    assert!(errors.is_empty());
+}
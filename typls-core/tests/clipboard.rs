@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use typls_core::{has_variables, ExpansionContext, SystemSource, MAX_CLIPBOARD_LENGTH};
+
+mod common;
+
+use common::expand_in;
+
+struct FakeClipboard {
+    text: Option<String>,
+    reads: AtomicUsize,
+}
+
+impl SystemSource for FakeClipboard {
+    fn clipboard(&self) -> Option<String> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.text.clone()
+    }
+}
+
+fn expand_with_clipboard(text: &str, clipboard: Option<&str>) -> (String, usize) {
+    let source = Arc::new(FakeClipboard {
+        text: clipboard.map(|text| text.to_string()),
+        reads: AtomicUsize::new(0),
+    });
+    let context = ExpansionContext::default().with_system(source.clone());
+
+    let expanded = expand_in(text, "c", &context).unwrap();
+
+    (expanded, source.reads.load(Ordering::SeqCst))
+}
+
+#[test]
+fn inserts_clipboard_once() {
+    let (text, reads) =
+        expand_with_clipboard("[{clipboard}]({clipboard})", Some("https://typls.app"));
+
+    assert_eq!(text, "[https://typls.app](https://typls.app)");
+    assert_eq!(reads, 1);
+}
+
+#[test]
+fn trims_clipboard() {
+    let (text, _) = expand_with_clipboard("> {clipboard|trim}", Some("  quote\n"));
+
+    assert_eq!(text, "> quote");
+}
+
+#[test]
+fn does_not_read_clipboard_if_unused() {
+    let (_, reads) = expand_with_clipboard("Hello", Some("secret"));
+
+    assert_eq!(reads, 0);
+}
+
+#[test]
+fn skips_missing_or_large_contents() {
+    let (text, _) = expand_with_clipboard("[{clipboard=nothing}]", None);
+    assert_eq!(text, "[nothing]");
+
+    let large = "x".repeat(MAX_CLIPBOARD_LENGTH + 1);
+    let (text, _) = expand_with_clipboard("[{clipboard}]", Some(&large));
+    assert_eq!(text, "[]");
+}
+
+#[test]
+fn clipboard_needs_no_parameters() {
+    assert!(!has_variables("> {clipboard|trim}"));
+}
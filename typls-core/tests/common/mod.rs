@@ -0,0 +1,61 @@
+//! Helpers shared by the integration tests. Every test file uses only some
+//! of them.
+#![allow(dead_code)]
+
+use typls_core::{
+    default_settings, expand, AppSettings, CaptureSignal, Expansion, ExpansionContext,
+};
+
+/// The default settings with a single expansion of `text` for `abbr`.
+pub fn settings_with(abbr: &str, text: &str) -> AppSettings {
+    settings_with_all(&[(abbr, text)])
+}
+
+/// The default settings with an expansion for each `(abbr, text)`.
+pub fn settings_with_all(expansions: &[(&str, &str)]) -> AppSettings {
+    let mut settings = default_settings();
+    for (abbr, text) in expansions {
+        settings.expansions.push(Expansion {
+            abbr: abbr.to_string(),
+            text: text.to_string(),
+            ..Default::default()
+        });
+    }
+
+    settings
+}
+
+/// The capture of `sequence`, ended by typing `append`.
+pub fn signal(sequence: &str, append: &str) -> CaptureSignal {
+    CaptureSignal {
+        sequence: sequence.to_string(),
+        append: append.to_string(),
+        append_enter: false,
+    }
+}
+
+/// Expand the sequence with a single expansion of `text`, whose
+/// abbreviation is the one the sequence starts with, like `hi` for
+/// `hi|Peter`.
+pub fn expand_in(text: &str, sequence: &str, context: &ExpansionContext) -> Option<String> {
+    let separator = default_settings().variables.separator;
+    let abbr = sequence
+        .split(separator.as_str())
+        .next()
+        .unwrap_or_default();
+
+    expand(sequence, &settings_with(abbr, text), context)
+}
+
+/// Like [`expand_in`], without any built-in values.
+pub fn expand_text(text: &str, sequence: &str) -> Option<String> {
+    expand_in(text, sequence, &ExpansionContext::default())
+}
+
+/// Like [`expand_text`], together with the errors reported while expanding.
+pub fn expand_with_errors(text: &str, sequence: &str) -> (Option<String>, Vec<String>) {
+    let context = ExpansionContext::default();
+    let text = expand_in(text, sequence, &context);
+
+    (text, context.take_errors())
+}
@@ -0,0 +1,75 @@
+use chrono::{Local, TimeZone, Utc};
+use typls_core::{has_variables, ExpansionContext};
+
+mod common;
+
+fn expand_text(text: &str, sequence: &str) -> String {
+    let context =
+        ExpansionContext::default().with_now(Utc.with_ymd_and_hms(2024, 5, 17, 10, 30, 0).unwrap());
+
+    common::expand_in(text, sequence, &context).unwrap()
+}
+
+#[test]
+fn formats_date_and_time() {
+    assert_eq!(expand_text("{date:UTC:%Y-%m-%d}", "d"), "2024-05-17");
+    assert_eq!(expand_text("{time:UTC}", "d"), "10:30");
+    assert_eq!(expand_text("{date:UTC:%d %B %Y}!", "d"), "17 May 2024!");
+}
+
+#[test]
+fn uses_local_time_by_default() {
+    let now = Utc.with_ymd_and_hms(2024, 5, 17, 10, 30, 0).unwrap();
+    let local = now.with_timezone(&Local);
+
+    assert_eq!(
+        expand_text("{date} {time}", "d"),
+        local.format("%Y-%m-%d %H:%M").to_string()
+    );
+}
+
+#[test]
+fn applies_offsets_and_time_zones() {
+    assert_eq!(expand_text("{date:+1d:UTC:%A}", "d"), "Saturday");
+    assert_eq!(expand_text("{date:-2w:UTC}", "d"), "2024-05-03");
+    assert_eq!(expand_text("{date:+1M:UTC:%B}", "d"), "June");
+    assert_eq!(expand_text("{time:Europe/Berlin:%H:%M}", "d"), "12:30");
+    assert_eq!(expand_text("{time:+90m:UTC}", "d"), "12:00");
+}
+
+#[test]
+fn parameters_override_builtins() {
+    assert_eq!(
+        expand_text("Due {date:%A}", "d|date=tomorrow"),
+        "Due tomorrow"
+    );
+}
+
+#[test]
+fn invalid_format_uses_default() {
+    assert_eq!(expand_text("[{date:%Q=none}]", "d"), "[none]");
+}
+
+#[test]
+fn reports_invalid_arguments() {
+    let cases = [
+        ("{date:%Q}", "Invalid date format `%Q`"),
+        ("{date:+7x:%A}", "`+7x` is no offset"),
+        ("{time:Europe/Berln}", "`Europe/Berln` is no offset"),
+        ("{date:+999999y}", "out of the supported date range"),
+    ];
+
+    for (text, error) in cases {
+        let (result, errors) = common::expand_with_errors(text, "d");
+
+        assert_eq!(result.as_deref(), Some(""));
+        assert_eq!(errors.len(), 1, "{}", text);
+        assert!(errors[0].contains(error), "{}", errors[0]);
+    }
+}
+
+#[test]
+fn builtins_do_not_need_parameters() {
+    assert!(!has_variables("Today is {date}, {time:%H:%M}"));
+    assert!(has_variables("Today is {date}, {name}"));
+}
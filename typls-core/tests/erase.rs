@@ -0,0 +1,85 @@
+use typls_core::{
+    plan_expansion, AppSettings, Capture, CaptureSignal, ExpansionContext, Key, KeyEvent,
+};
+
+mod common;
+
+use common::{settings_with_all, signal};
+
+fn settings() -> AppSettings {
+    settings_with_all(&[("sig", "Best regards, {}"), ("café", "Coffee")])
+}
+
+fn erase_count(sequence: &str, append: &str, append_enter: bool) -> usize {
+    let signal = CaptureSignal {
+        append_enter,
+        ..signal(sequence, append)
+    };
+
+    plan_expansion(&signal, &settings(), &ExpansionContext::default())
+        .unwrap()
+        .erase
+}
+
+#[test]
+fn erases_ascii_sequence() {
+    // ' + sig + space
+    assert_eq!(erase_count("sig", " ", false), 5);
+    // ' + sig + enter
+    assert_eq!(erase_count("sig", "", true), 5);
+}
+
+#[test]
+fn erases_multibyte_parameter() {
+    // ' + sig|Jürgen + space
+    assert_eq!(erase_count("sig|Jürgen", " ", false), 12);
+}
+
+#[test]
+fn erases_multibyte_abbreviation() {
+    assert_eq!(erase_count("café", ".", false), 6);
+}
+
+#[test]
+fn erases_combining_characters_as_one() {
+    // "e" followed by a combining acute accent is a single grapheme.
+    assert_eq!(erase_count("sig|Rene\u{301}", " ", false), 10);
+}
+
+#[test]
+fn erases_emoji_as_one() {
+    // Emoji with skin tone modifier and a ZWJ family sequence.
+    assert_eq!(erase_count("sig|👍🏽", "", false), 6);
+    assert_eq!(erase_count("sig|👨‍👩‍👧", " ", false), 7);
+}
+
+#[test]
+fn erases_multibyte_trigger() {
+    let mut settings = settings();
+    settings.trigger.string = "§".to_string();
+
+    let plan =
+        plan_expansion(&signal("sig", " "), &settings, &ExpansionContext::default()).unwrap();
+    assert_eq!(plan.erase, 5);
+}
+
+#[test]
+fn backspace_removes_whole_grapheme() {
+    let settings = settings();
+    let mut capture = Capture::new();
+
+    for event in [
+        KeyEvent::text("'"),
+        KeyEvent::text("s"),
+        KeyEvent::text("i"),
+        KeyEvent::text("g"),
+        KeyEvent::text("|"),
+        KeyEvent::text("👍🏽"),
+        KeyEvent::key(Key::Backspace),
+        KeyEvent::text("ü"),
+    ] {
+        assert_eq!(capture.handle(&event, &settings), None);
+    }
+
+    assert_eq!(capture.sequence(), "sig|ü");
+}
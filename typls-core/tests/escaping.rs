@@ -0,0 +1,149 @@
+use typls_core::{
+    default_settings, has_variables, plan_expansion, split_sequence, tokenize, Capture,
+    CaptureSignal, ExpansionContext, KeyEvent, Params, Token,
+};
+
+mod common;
+
+use common::expand_text;
+
+fn params(sequence: &str) -> Params {
+    split_sequence(sequence, &default_settings()).1
+}
+
+fn named(pairs: &[(&str, &str)]) -> Params {
+    Params {
+        unnamed: vec![],
+        named: pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+    }
+}
+
+#[test]
+fn escapes_variables_with_backslash() {
+    assert_eq!(
+        tokenize(r"Use \{name} for {name}").unwrap(),
+        vec![
+            Token::Text("Use {name} for ".to_string()),
+            tokenize("{name}").unwrap()[0].clone(),
+        ]
+    );
+
+    assert_eq!(
+        expand_text(r"\{name} \\{name} \\\{name}", "e|name=x").unwrap(),
+        r"{name} \x \{name}"
+    );
+    assert_eq!(
+        expand_text(r"\{?a}\{/a} \{id:/[a-z]{3}/}", "e").unwrap(),
+        "{?a}{/a} {id:/[a-z]{3}/}"
+    );
+    assert!(!has_variables(r"\{name} \{}"));
+}
+
+#[test]
+fn escapes_markers() {
+    let settings = common::settings_with("e", r"a\{|}b\{$1}{|}");
+
+    let signal = CaptureSignal {
+        sequence: "e".to_string(),
+        append: String::new(),
+        append_enter: false,
+    };
+    let plan = plan_expansion(&signal, &settings, &ExpansionContext::default()).unwrap();
+
+    assert_eq!(plan.text, "a{|}b{$1}");
+    assert!(plan.keys.is_empty());
+}
+
+#[test]
+fn escaped_variables_are_not_validated() {
+    assert_eq!(expand_text(r"\{name|shout}", "e").unwrap(), "{name|shout}");
+}
+
+#[test]
+fn keeps_other_backslashes() {
+    for text in [r"\{ b(); }", r"C:\\dir", r"\\{ x }", r"a\nb"] {
+        assert_eq!(expand_text(text, "e").unwrap(), text);
+    }
+}
+
+#[test]
+fn splits_named_parameters_at_first_equal_sign() {
+    assert_eq!(
+        params("e|url=https://x?a=b"),
+        named(&[("url", "https://x?a=b")])
+    );
+}
+
+#[test]
+fn quotes_parameters() {
+    assert_eq!(
+        params(r#"e|q="a|b"|"x=y"|r="say \"hi\" \\ \o/""#),
+        Params {
+            unnamed: vec!["x=y".to_string()],
+            named: named(&[("q", "a|b"), ("r", r#"say "hi" \ \o/"#)]).named,
+        }
+    );
+
+    assert_eq!(
+        params(r#"e|"a|b"c|d"#).unnamed,
+        vec!["a|bc".to_string(), "d".to_string()]
+    );
+    assert_eq!(params(r#"e|open="a|b"#), named(&[("open", "a|b")]));
+    assert_eq!(params(r"e|C:\dir").unnamed, vec![r"C:\dir".to_string()]);
+}
+
+#[test]
+fn quoted_parameters_are_expanded() {
+    assert_eq!(
+        expand_text("[{}]({url})", r#"e|"Docs | Home"|url="https://x?a=b""#).unwrap(),
+        "[Docs | Home](https://x?a=b)"
+    );
+}
+
+#[test]
+fn confirm_characters_in_quotes_are_captured() {
+    let settings = default_settings();
+    let mut capture = Capture::new();
+    let mut signals = vec![];
+
+    for c in r#"'e|url="https://x.org?a=b" "#.chars() {
+        if let Some(signal) = capture.handle(&KeyEvent::text(&c.to_string()), &settings) {
+            signals.push(signal);
+        }
+    }
+
+    assert_eq!(signals.len(), 1);
+    assert_eq!(signals[0].sequence, r#"e|url="https://x.org?a=b""#);
+    assert_eq!(signals[0].append, " ");
+}
+
+#[test]
+fn keeps_quotes_inside_values() {
+    assert_eq!(params(r#"e|v=say "hi""#), named(&[("v", r#"say "hi""#)]));
+    assert_eq!(
+        params(r#"e|5" screen|len=5"|x"#),
+        Params {
+            unnamed: vec![r#"5" screen"#.to_string(), "x".to_string()],
+            named: named(&[("len", r#"5""#)]).named,
+        }
+    );
+}
+
+#[test]
+fn unmatched_quotes_do_not_swallow_confirm_characters() {
+    let settings = default_settings();
+    let mut capture = Capture::new();
+    let mut signals = vec![];
+
+    for c in r#"'e|len=5" "#.chars() {
+        if let Some(signal) = capture.handle(&KeyEvent::text(&c.to_string()), &settings) {
+            signals.push(signal);
+        }
+    }
+
+    assert_eq!(signals.len(), 1);
+    assert_eq!(signals[0].sequence, r#"e|len=5""#);
+}
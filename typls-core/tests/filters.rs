@@ -0,0 +1,110 @@
+use typls_core::{has_variables, tokenize, Filter, Token, Variable};
+
+mod common;
+
+use common::expand_text;
+
+#[test]
+fn applies_filters() {
+    assert_eq!(
+        expand_text("{name|upper} {name|lower}", "f|name=Peter").unwrap(),
+        "PETER peter"
+    );
+    assert_eq!(
+        expand_text("{title|title}", "f|title=hello wORLD").unwrap(),
+        "Hello World"
+    );
+    assert_eq!(
+        expand_text("/blog/{title|slug}", "f|title=Hello, World! 2025").unwrap(),
+        "/blog/hello-world-2025"
+    );
+    assert_eq!(
+        expand_text("?q={q|urlencode}", "f|q=a b&c/ä").unwrap(),
+        "?q=a%20b%26c%2F%C3%A4"
+    );
+    assert_eq!(
+        expand_text("[{x|trim}]", "f|x=  spaced  ").unwrap(),
+        "[spaced]"
+    );
+    assert_eq!(
+        expand_text("{x|replace:-:_}", "f|x=a-b-c").unwrap(),
+        "a_b_c"
+    );
+}
+
+#[test]
+fn chains_filters_in_order() {
+    assert_eq!(
+        expand_text("{x|trim|replace:o:0|upper}", "f|x= foo ").unwrap(),
+        "F00"
+    );
+}
+
+#[test]
+fn applies_filters_to_defaults_and_unnamed_variables() {
+    assert_eq!(expand_text("{name|upper=anna}", "f").unwrap(), "ANNA");
+    assert_eq!(
+        expand_text("{|title} {}", "f|peter|pan").unwrap(),
+        "Peter pan"
+    );
+}
+
+#[test]
+fn fills_empty_defaults() {
+    assert_eq!(expand_text("```{lang=}", "f").unwrap(), "```");
+}
+
+#[test]
+fn rejects_unknown_filters() {
+    let (text, errors) = common::expand_with_errors("{name|shout}", "f|name=x");
+
+    assert_eq!(text, None);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("Unknown filter `shout`"));
+    assert!(errors[0].contains("{name|shout}"));
+
+    assert!(Filter::parse("replace:a").is_err());
+    assert!(Filter::parse("upper:x").is_err());
+}
+
+#[test]
+fn tokenizes_variables_markers_and_text() {
+    let tokens = tokenize("Hi {name|upper=you}, {|}{$1}").unwrap();
+
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Text("Hi ".to_string()),
+            Token::Variable(Variable {
+                name: "name".to_string(),
+                default: "you".to_string(),
+                filters: vec![Filter::Upper],
+                choices: vec![],
+                kind: None,
+            }),
+            Token::Text(", ".to_string()),
+            Token::Marker("{|}".to_string()),
+            Token::Marker("{$1}".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn keeps_code_braces_literal() {
+    for text in [
+        "if (a) { b(); }",
+        "{a || b}",
+        r#"{"key": 1}"#,
+        "{ name }",
+        "{{",
+        "}{",
+    ] {
+        assert_eq!(tokenize(text).unwrap(), vec![Token::Text(text.to_string())]);
+        assert!(!has_variables(text), "{}", text);
+    }
+
+    assert_eq!(
+        expand_text("fn main() {{x}}", "f|x=y").unwrap(),
+        "fn main() {y}"
+    );
+}
@@ -0,0 +1,104 @@
+use typls_core::{
+    plan_form, variable_form, AppSettings, CaptureSignal, ExpansionContext, FormField, Params,
+};
+
+mod common;
+
+use common::settings_with;
+
+const TEXT: &str =
+    "{tone:formal|casual}: Hi {name}, {} {?company}from {company=ACME}{/company} {date:%Y}";
+
+fn settings() -> AppSettings {
+    settings_with("mail", TEXT)
+}
+
+fn signal(sequence: &str) -> CaptureSignal {
+    common::signal(sequence, " ")
+}
+
+#[test]
+fn lists_variables_in_order_with_defaults() {
+    let form = variable_form(
+        &signal("mail|name=Anna"),
+        &settings(),
+        &ExpansionContext::default(),
+    )
+    .unwrap();
+
+    assert_eq!(form.abbr, "mail");
+    assert_eq!(
+        form.fields,
+        vec![
+            FormField {
+                name: "tone".to_string(),
+                default: "formal".to_string(),
+                value: None,
+                choices: vec!["formal".to_string(), "casual".to_string()],
+            },
+            FormField {
+                name: "name".to_string(),
+                default: String::new(),
+                value: Some("Anna".to_string()),
+                choices: vec![],
+            },
+            FormField {
+                name: String::new(),
+                default: String::new(),
+                value: None,
+                choices: vec![],
+            },
+            FormField {
+                name: "company".to_string(),
+                default: "ACME".to_string(),
+                value: None,
+                choices: vec![],
+            },
+        ]
+    );
+}
+
+#[test]
+fn needs_no_form_if_everything_was_typed() {
+    let settings = settings();
+    let context = ExpansionContext::default();
+
+    assert!(variable_form(
+        &signal("mail|tone=casual|name=Anna|company=|thanks"),
+        &settings,
+        &context
+    )
+    .is_none());
+    assert!(variable_form(&signal("nope"), &settings, &context).is_none());
+}
+
+#[test]
+fn plans_with_submitted_values() {
+    let settings = settings();
+    let context = ExpansionContext::default();
+    let signal = signal("mail|name=Anna");
+
+    let form = variable_form(&signal, &settings, &context).unwrap();
+    let params = form.fill(vec![
+        "2".to_string(),
+        "Bob".to_string(),
+        "thanks".to_string(),
+        String::new(),
+    ]);
+
+    assert_eq!(
+        params,
+        Params {
+            unnamed: vec!["thanks".to_string()],
+            named: [("tone", "2"), ("name", "Bob"), ("company", "")]
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    );
+
+    let plan = plan_form(&signal, params, &settings, &context).unwrap();
+
+    assert!(plan.text.starts_with("casual: Hi Bob, thanks  "));
+    assert_eq!(plan.erase, "'mail|name=Anna ".len());
+}
@@ -0,0 +1,148 @@
+use typls_core::format::html_to_plain;
+use typls_core::{plan_expansion, ExpansionContext, ExpansionFormat, ExpansionPlan, RichText};
+
+mod common;
+
+use common::{settings_with, signal};
+
+fn plan(
+    format: ExpansionFormat,
+    text: &str,
+    sequence: &str,
+    context: &ExpansionContext,
+) -> Option<ExpansionPlan> {
+    let mut settings = settings_with("e", text);
+    settings.expansions.last_mut().unwrap().format = format;
+
+    plan_expansion(&signal(sequence, ""), &settings, context)
+}
+
+fn rich_text(format: ExpansionFormat, text: &str, sequence: &str) -> (String, RichText) {
+    let plan = plan(format, text, sequence, &ExpansionContext::default()).unwrap();
+
+    (plan.text, plan.rich.unwrap())
+}
+
+#[test]
+fn escapes_values_in_html() {
+    let (text, rich) = rich_text(ExpansionFormat::Html, "<p>Hi {name}</p>", "e|name=a<b & c");
+
+    assert_eq!(rich.html, "<p>Hi a&lt;b &amp; c</p>");
+    assert_eq!(rich.plain, "Hi a<b & c");
+    assert_eq!(text, rich.plain);
+}
+
+#[test]
+fn escapes_values_in_markdown() {
+    let (_, rich) = rich_text(
+        ExpansionFormat::Markdown,
+        "**{name}**: {note}",
+        "e|name=*a_b*|note=1 < 2 & [x](y)",
+    );
+
+    assert_eq!(
+        rich.html,
+        "<p><strong>*a_b*</strong>: 1 &lt; 2 &amp; [x](y)</p>"
+    );
+    assert_eq!(rich.plain, "*a_b*: 1 < 2 & [x](y)");
+}
+
+#[test]
+fn keeps_values_in_markdown_code_as_they_are() {
+    let (_, rich) = rich_text(
+        ExpansionFormat::Markdown,
+        "Run `{cmd}`:\n\n```\n{cmd}\n```",
+        "e|cmd=git log -1 --format=%h",
+    );
+
+    assert_eq!(
+        rich.html,
+        "<p>Run <code>git log -1 --format=%h</code>:</p>\n<pre><code>git log -1 --format=%h\n</code></pre>"
+    );
+    assert_eq!(
+        rich.plain,
+        "Run git log -1 --format=%h:\n\ngit log -1 --format=%h"
+    );
+}
+
+#[test]
+fn escapes_values_in_markdown_html_as_html() {
+    let (_, rich) = rich_text(
+        ExpansionFormat::Markdown,
+        "<abbr title=\"{v}\">*{v}*</abbr>",
+        "e|v=a_b & \"c\"",
+    );
+
+    assert_eq!(
+        rich.html,
+        "<p><abbr title=\"a_b &amp; &quot;c&quot;\"><em>a_b &amp; \"c\"</em></abbr></p>"
+    );
+}
+
+#[test]
+fn escaped_values_work_in_markdown_links() {
+    let (_, rich) = rich_text(
+        ExpansionFormat::Markdown,
+        "[{text}]({url})",
+        "e|text=a_b|url=https://x.org/a_b?c=d&e=f",
+    );
+
+    assert_eq!(
+        rich.html,
+        "<p><a href=\"https://x.org/a_b?c=d&amp;e=f\">a_b</a></p>"
+    );
+}
+
+#[test]
+fn rejects_markers() {
+    for (format, text) in [
+        (ExpansionFormat::Markdown, "- {|}a\n- b"),
+        (ExpansionFormat::Markdown, "Dear {$1},\n\n{$2}"),
+        (ExpansionFormat::Html, "<p>{|}</p>"),
+    ] {
+        let context = ExpansionContext::default();
+
+        assert_eq!(plan(format, text, "e", &context), None);
+
+        let errors = context.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].contains("only works in plain text"),
+            "{}",
+            errors[0]
+        );
+    }
+}
+
+#[test]
+fn converts_lists_to_dashes() {
+    assert_eq!(
+        html_to_plain("<p>Todo:</p><ul>\n<li>one</li>\n<li>two <b>now</b></li>\n</ul><p>Done</p>"),
+        "Todo:\n\n- one\n- two now\n\nDone"
+    );
+}
+
+#[test]
+fn decodes_entities() {
+    assert_eq!(
+        html_to_plain("a &lt;b&gt; &amp;&nbsp;&quot;c&quot; &#39;d&#x27; &unknown; & e"),
+        "a <b> & \"c\" 'd' &unknown; & e"
+    );
+}
+
+#[test]
+fn keeps_whitespace_in_pre() {
+    assert_eq!(
+        html_to_plain("<p>Run:</p><pre><code>a\n  b\n</code></pre><p>x   y</p>"),
+        "Run:\n\na\n  b\n\nx y"
+    );
+    assert_eq!(html_to_plain("<pre>\n  indented</pre>"), "  indented");
+}
+
+#[test]
+fn breaks_lines_at_br() {
+    assert_eq!(
+        html_to_plain("Dear Anna, <br>thanks!<br/>\n  Bob"),
+        "Dear Anna,\nthanks!\nBob"
+    );
+}
@@ -0,0 +1,41 @@
+use std::thread;
+use std::time::Duration;
+
+use typls_core::InjectionGuard;
+
+#[test]
+fn allows_one_injection_at_a_time() {
+    let guard = InjectionGuard::new(Duration::ZERO);
+    let injection = guard.begin().unwrap();
+
+    assert!(guard.begin().is_none());
+    // Clones share the state, like the listener and the executor do.
+    assert!(guard.clone().begin().is_none());
+
+    drop(injection);
+    assert!(guard.begin().is_some());
+}
+
+#[test]
+fn stays_active_during_the_grace_period() {
+    let guard = InjectionGuard::new(Duration::from_millis(50));
+    assert!(!guard.is_active());
+
+    let injection = guard.begin().unwrap();
+    assert!(guard.is_active());
+
+    drop(injection);
+    assert!(guard.is_active());
+
+    thread::sleep(Duration::from_millis(80));
+    assert!(!guard.is_active());
+}
+
+#[test]
+fn can_begin_during_the_grace_period() {
+    let guard = InjectionGuard::new(Duration::from_secs(60));
+    drop(guard.begin().unwrap());
+
+    assert!(guard.is_active());
+    assert!(guard.begin().is_some());
+}
@@ -0,0 +1,77 @@
+use typls_core::{plan_expansion, ActiveApp, ExpansionContext, History, HistoryEntry};
+
+mod common;
+
+use common::{settings_with, signal};
+
+fn entry(sequence: &str) -> HistoryEntry {
+    let settings = settings_with("hi", "Hello {}, {kind=nice} to meet you");
+    let signal = signal(sequence, "");
+    let active_app = ActiveApp {
+        process_path: "/usr/bin/editor".to_string(),
+        platform: "linux".to_string(),
+        ..Default::default()
+    };
+    let plan = plan_expansion(
+        &signal,
+        &settings,
+        &ExpansionContext::new(active_app.clone()),
+    )
+    .unwrap();
+
+    HistoryEntry::new(&signal, &plan, &settings, &active_app)
+}
+
+#[test]
+fn records_parameters_and_text() {
+    let entry = entry("hi|Peter|kind=great");
+
+    assert_eq!(entry.abbr, "hi");
+    assert_eq!(entry.params.unnamed, vec!["Peter"]);
+    assert_eq!(entry.params.named["kind"], "great");
+    assert_eq!(entry.text, "Hello Peter, great to meet you");
+    assert_eq!(entry.app, "/usr/bin/editor");
+
+    let plan = entry.plan();
+    assert_eq!(plan.erase, 0);
+    assert_eq!(plan.text, entry.text);
+}
+
+#[test]
+fn keeps_most_recent_entries() {
+    let mut history = History::new();
+
+    for name in ["Anna", "Ben", "Carla"] {
+        history.push(entry(&format!("hi|{}", name)), 2);
+    }
+
+    let texts = history
+        .entries()
+        .into_iter()
+        .map(|entry| entry.params.unnamed[0].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(texts, vec!["Carla", "Ben"]);
+
+    assert_eq!(history.search("ben").len(), 1);
+    assert_eq!(history.search("meet").len(), 2);
+
+    let id = history.entries()[0].id.clone();
+    assert!(history.get(&id).is_some());
+
+    history.clear();
+    assert!(history.entries().is_empty());
+}
+
+#[test]
+fn saves_and_loads_file() {
+    let path = std::env::temp_dir().join(format!("typls-history-{}.json", std::process::id()));
+
+    let mut history = History::new();
+    history.push(entry("hi|Peter"), 10);
+    history.save(&path).unwrap();
+
+    let loaded = History::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.entries(), history.entries());
+}
@@ -0,0 +1,112 @@
+use typls_core::{
+    default_settings, resolve_output, AppSettings, Expansion, ExpansionFormat, Group, OutputKind,
+};
+
+fn output_settings(global: OutputKind, group: Option<OutputKind>) -> AppSettings {
+    let mut settings = default_settings();
+    settings.output.kind = global;
+    settings.groups = Some(vec![Group {
+        id: "g".to_string(),
+        output: group,
+        ..Default::default()
+    }]);
+
+    settings
+}
+
+fn expansion(group: Option<&str>, output: Option<OutputKind>) -> Expansion {
+    Expansion {
+        abbr: "e".to_string(),
+        group: group.map(|group| group.to_string()),
+        output,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn prefers_expansion_over_group_over_global() {
+    let settings = output_settings(OutputKind::Enigo, Some(OutputKind::Uinput));
+
+    assert_eq!(
+        resolve_output(&settings, &expansion(None, None), "hi"),
+        OutputKind::Enigo
+    );
+    assert_eq!(
+        resolve_output(&settings, &expansion(Some("g"), None), "hi"),
+        OutputKind::Uinput
+    );
+    assert_eq!(
+        resolve_output(
+            &settings,
+            &expansion(Some("g"), Some(OutputKind::Clipboard)),
+            "hi"
+        ),
+        OutputKind::Clipboard
+    );
+
+    // Groups without their own output and unknown groups use the global one.
+    let settings_without_group_output = output_settings(OutputKind::Uinput, None);
+    assert_eq!(
+        resolve_output(
+            &settings_without_group_output,
+            &expansion(Some("g"), None),
+            "hi"
+        ),
+        OutputKind::Uinput
+    );
+    assert_eq!(
+        resolve_output(&settings, &expansion(Some("unknown"), None), "hi"),
+        OutputKind::Enigo
+    );
+}
+
+#[test]
+fn pastes_long_texts() {
+    let mut settings = output_settings(OutputKind::Enigo, Some(OutputKind::Uinput));
+    settings.output.paste_threshold = Some(5);
+
+    assert_eq!(
+        resolve_output(&settings, &expansion(None, None), "abcd"),
+        OutputKind::Enigo
+    );
+    assert_eq!(
+        resolve_output(&settings, &expansion(None, None), "abcde"),
+        OutputKind::Clipboard
+    );
+    // Counted in graphemes, not bytes.
+    assert_eq!(
+        resolve_output(&settings, &expansion(None, None), "äöü👍🏽"),
+        OutputKind::Enigo
+    );
+    assert_eq!(
+        resolve_output(&settings, &expansion(Some("g"), None), "abcdef"),
+        OutputKind::Clipboard
+    );
+
+    // The expansion's own output wins.
+    assert_eq!(
+        resolve_output(
+            &settings,
+            &expansion(None, Some(OutputKind::Uinput)),
+            "abcdef"
+        ),
+        OutputKind::Uinput
+    );
+}
+
+#[test]
+fn formatted_expansions_are_pasted() {
+    let settings = output_settings(OutputKind::Enigo, None);
+
+    for format in [ExpansionFormat::Markdown, ExpansionFormat::Html] {
+        let expansion = Expansion {
+            format,
+            ..expansion(None, Some(OutputKind::Uinput))
+        };
+
+        assert_eq!(
+            resolve_output(&settings, &expansion, "hi"),
+            OutputKind::Clipboard
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use typls_core::{default_settings, KeyEvent, PhraseAnalyzer};
+
+fn type_text(analyzer: &mut PhraseAnalyzer, text: &str) {
+    for c in text.chars() {
+        analyzer.handle(&KeyEvent::text(&c.to_string()), 3);
+    }
+}
+
+#[test]
+fn suggests_repeated_phrases() {
+    let mut analyzer = PhraseAnalyzer::new();
+
+    for _ in 0..3 {
+        type_text(&mut analyzer, "Thanks. Best regards from Berlin. Bye. ");
+    }
+
+    let suggestions = analyzer.suggestions(&default_settings(), 3);
+
+    assert_eq!(suggestions.len(), 1);
+    assert_eq!(suggestions[0].expansion.text, "Best regards from Berlin");
+    assert_eq!(suggestions[0].expansion.abbr, "brfb");
+    assert_eq!(suggestions[0].count, 3);
+}
+
+#[test]
+fn ignores_rare_phrases() {
+    let mut analyzer = PhraseAnalyzer::new();
+
+    type_text(&mut analyzer, "Best regards from Berlin. ");
+    type_text(&mut analyzer, "Best regards from Berlin. ");
+
+    assert!(analyzer.suggestions(&default_settings(), 3).is_empty());
+}
+
+#[test]
+fn saves_only_hashes() {
+    let path = std::env::temp_dir().join(format!("typls-phrases-{}.json", std::process::id()));
+
+    let mut analyzer = PhraseAnalyzer::new();
+    for _ in 0..3 {
+        type_text(&mut analyzer, "Best regards from Berlin. ");
+    }
+    analyzer.save(&path).unwrap();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(!contents.to_lowercase().contains("regards"));
+    assert!(!contents.contains(analyzer.salt()));
+
+    // The text is learned again the next time the phrase is typed.
+    let mut analyzer = PhraseAnalyzer::from_file(&path, analyzer.salt()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(analyzer.suggestions(&default_settings(), 3).is_empty());
+
+    type_text(&mut analyzer, "Best regards from Berlin. ");
+    assert_eq!(analyzer.suggestions(&default_settings(), 3)[0].count, 4);
+}
+
+#[test]
+fn counts_are_lost_without_their_salt() {
+    let path = std::env::temp_dir().join(format!("typls-salt-{}.json", std::process::id()));
+
+    let mut analyzer = PhraseAnalyzer::new();
+    for _ in 0..3 {
+        type_text(&mut analyzer, "Best regards from Berlin. ");
+    }
+    analyzer.save(&path).unwrap();
+
+    let mut analyzer = PhraseAnalyzer::from_file(&path, "other salt").unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    type_text(&mut analyzer, "Best regards from Berlin. ");
+    assert!(analyzer.suggestions(&default_settings(), 3).is_empty());
+}
+
+#[test]
+fn requests_each_save_once() {
+    let mut analyzer = PhraseAnalyzer::new();
+
+    let mut requests = 0;
+    for _ in 0..250 {
+        type_text(&mut analyzer, "word ");
+        if analyzer.take_save_request() {
+            requests += 1;
+        }
+    }
+
+    assert_eq!(requests, 2);
+}
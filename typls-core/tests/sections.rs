@@ -0,0 +1,113 @@
+use typls_core::{has_variables, parse_template, parse_variables, Condition, Expansion, Node};
+
+mod common;
+
+use common::expand_text;
+
+#[test]
+fn shows_section_if_variable_is_present() {
+    let text = "Hi {name}{?company}, from {company}{/company}!";
+
+    assert_eq!(
+        expand_text(text, "hi|name=Anna|company=ACME").unwrap(),
+        "Hi Anna, from ACME!"
+    );
+    assert_eq!(expand_text(text, "hi|name=Anna").unwrap(), "Hi Anna!");
+    assert_eq!(
+        expand_text(text, "hi|name=Anna|company=").unwrap(),
+        "Hi Anna!"
+    );
+}
+
+#[test]
+fn shows_else_part_otherwise() {
+    let text = "{?company}Team {company}{:else}Freelancer{/company}";
+
+    assert_eq!(expand_text(text, "hi|company=ACME").unwrap(), "Team ACME");
+    assert_eq!(expand_text(text, "hi").unwrap(), "Freelancer");
+}
+
+#[test]
+fn tests_for_values_and_empty_variables() {
+    let text = "{?tone=formal}Dear{:else}Hey{/tone} {name}{?name=}you{/name}";
+
+    assert_eq!(
+        expand_text(text, "hi|tone=formal|name=Ms. Doe").unwrap(),
+        "Dear Ms. Doe"
+    );
+    assert_eq!(expand_text(text, "hi|tone=casual").unwrap(), "Hey you");
+}
+
+#[test]
+fn uses_defaults_for_conditions() {
+    let text = "{?tone=formal}Dear{:else}Hey{/tone} {name=there} ({tone=formal})";
+
+    assert_eq!(expand_text(text, "hi").unwrap(), "Dear there (formal)");
+    assert_eq!(
+        expand_text(text, "hi|tone=casual").unwrap(),
+        "Hey there (casual)"
+    );
+}
+
+#[test]
+fn nests_sections() {
+    let text = "{?a}A{?b}B{:else}-{/b}{/a}.";
+
+    assert_eq!(expand_text(text, "hi|a=1|b=1").unwrap(), "AB.");
+    assert_eq!(expand_text(text, "hi|a=1").unwrap(), "A-.");
+    assert_eq!(expand_text(text, "hi|b=1").unwrap(), ".");
+}
+
+#[test]
+fn rejects_bad_nesting() {
+    let errors = [
+        ("{?a}x", "never closed"),
+        ("x{/a}", "without"),
+        ("{?a}{?b}{/a}{/b}", "closes"),
+        ("{?a}x{:else}y{:else}z{/a}", "Second"),
+        ("x{:else}y", "outside"),
+    ];
+
+    for (text, error) in errors {
+        let result = parse_template(text);
+        assert!(
+            result.as_ref().is_err_and(|e| e.contains(error)),
+            "{}: {:?}",
+            text,
+            result
+        );
+    }
+
+    let (text, errors) = common::expand_with_errors("{?a}x", "hi");
+
+    assert_eq!(text, None);
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn parses_sections_into_tree() {
+    assert_eq!(
+        parse_template("{?a=1}x{:else}y{/a}").unwrap(),
+        vec![Node::Section {
+            condition: Condition {
+                name: "a".to_string(),
+                value: Some("1".to_string()),
+            },
+            then: vec![Node::Text("x".to_string())],
+            otherwise: vec![Node::Text("y".to_string())],
+        }]
+    );
+}
+
+#[test]
+fn lists_condition_variables() {
+    let variables = parse_variables(&Expansion {
+        text: "{?company}, from ACME{/company}".to_string(),
+        ..Default::default()
+    })
+    .unwrap();
+
+    assert!(variables.named.contains_key("company"));
+    assert!(has_variables("{?company}, from ACME{/company}"));
+    assert!(!has_variables("{?clipboard}{clipboard}{/clipboard}"));
+}
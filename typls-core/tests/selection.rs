@@ -0,0 +1,135 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use typls_core::{
+    plan_selection, AppSettings, ExpansionContext, Key, PlanKey, SelectionHotkey, SystemSource,
+};
+
+mod common;
+
+use common::{settings_with, settings_with_all};
+
+struct FakeSelection {
+    text: Option<String>,
+    copies: AtomicUsize,
+}
+
+impl SystemSource for FakeSelection {
+    fn clipboard(&self) -> Option<String> {
+        Some("clipboard".to_string())
+    }
+
+    fn selection(&self) -> Option<String> {
+        self.copies.fetch_add(1, Ordering::SeqCst);
+        self.text.clone()
+    }
+}
+
+fn settings() -> AppSettings {
+    settings_with_all(&[
+        ("fence", "```{lang=text}\n{selection}\n```"),
+        ("link", "[{selection=link}]({|})"),
+    ])
+}
+
+fn context(selection: Option<&str>) -> (ExpansionContext, Arc<FakeSelection>) {
+    let source = Arc::new(FakeSelection {
+        text: selection.map(|text| text.to_string()),
+        copies: AtomicUsize::new(0),
+    });
+
+    (
+        ExpansionContext::default().with_system(source.clone()),
+        source,
+    )
+}
+
+#[test]
+fn replaces_selection_without_erasing() {
+    let (context, source) = context(Some("let x = 1;"));
+    let plan = plan_selection("fence|lang=rust", &settings(), &context).unwrap();
+
+    assert_eq!(plan.erase, 0);
+    assert_eq!(plan.text, "```rust\nlet x = 1;\n```");
+    assert_eq!(source.copies.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn uses_default_without_selection() {
+    let (context, _) = context(None);
+    let plan = plan_selection("link", &settings(), &context).unwrap();
+
+    assert_eq!(plan.text, "[link]()");
+    assert_eq!(plan.keys, vec![PlanKey::Left]);
+}
+
+#[test]
+fn copies_only_if_used() {
+    let settings = settings_with("sig", "Best regards");
+
+    let (context, source) = context(Some("text"));
+    plan_selection("sig", &settings, &context).unwrap();
+
+    assert_eq!(source.copies.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn finds_hotkey_for_key() {
+    let mut settings = settings();
+    settings.selection.hotkeys = vec![
+        SelectionHotkey {
+            key: Key::Function(8),
+            sequence: "fence".to_string(),
+        },
+        SelectionHotkey {
+            key: Key::Other,
+            sequence: "link".to_string(),
+        },
+    ];
+
+    let hotkey = settings.selection.hotkey(Key::Function(8)).unwrap();
+    assert_eq!(hotkey.sequence, "fence");
+
+    assert!(settings.selection.hotkey(Key::Function(9)).is_none());
+    assert!(settings.selection.hotkey(Key::Other).is_none());
+}
+
+#[test]
+fn only_function_keys_are_hotkeys() {
+    let mut settings = settings();
+    settings.selection.hotkeys = [
+        Key::Modifier,
+        Key::Backspace,
+        Key::Return,
+        Key::Tab,
+        Key::LeftArrow,
+    ]
+    .into_iter()
+    .map(|key| SelectionHotkey {
+        key,
+        sequence: "fence".to_string(),
+    })
+    .collect();
+
+    for hotkey in settings.selection.hotkeys.clone() {
+        assert!(settings.selection.hotkey(hotkey.key).is_none());
+    }
+    assert_eq!(settings.selection.invalid_hotkeys().count(), 5);
+}
+
+#[test]
+fn removes_invalid_hotkeys() {
+    let mut settings = settings();
+    let valid = SelectionHotkey {
+        key: Key::Function(8),
+        sequence: "fence".to_string(),
+    };
+    let invalid = SelectionHotkey {
+        key: Key::Backspace,
+        sequence: "link".to_string(),
+    };
+    settings.selection.hotkeys = vec![invalid.clone(), valid.clone()];
+
+    assert_eq!(settings.selection.remove_invalid_hotkeys(), vec![invalid]);
+    assert_eq!(settings.selection.hotkeys, vec![valid]);
+}
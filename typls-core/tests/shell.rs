@@ -0,0 +1,150 @@
+#![cfg(unix)]
+
+use std::time::{Duration, Instant};
+
+use typls_core::{
+    has_variables, run_command, tokenize, ExpansionContext, ShellPermissions, ShellSettings, Token,
+    Variable,
+};
+
+mod common;
+
+use common::expand_in;
+
+fn context(permissions: ShellPermissions) -> ExpansionContext {
+    ExpansionContext::default().with_shell(permissions, ShellSettings::default())
+}
+
+#[test]
+fn inserts_output_of_allowed_command() {
+    let mut permissions = ShellPermissions::new();
+    permissions.allow("echo main", None);
+
+    let context = context(permissions);
+    let text = expand_in("On {shell:echo main}.", "b", &context).unwrap();
+
+    assert_eq!(text, "On main.");
+    assert!(context.take_errors().is_empty());
+}
+
+#[test]
+fn allows_commands_per_expansion() {
+    let mut permissions = ShellPermissions::new();
+    permissions.allow("echo main", Some("branch"));
+
+    assert!(permissions.is_allowed("echo main", "branch"));
+    assert!(!permissions.is_allowed("echo main", "other"));
+
+    permissions.revoke("echo main", Some("branch"));
+    assert!(!permissions.is_allowed("echo main", "branch"));
+}
+
+#[test]
+fn does_not_run_commands_that_are_not_allowed() {
+    let path = std::env::temp_dir().join(format!("typls-shell-{}", std::process::id()));
+    let command = format!("touch {}", path.display());
+
+    let context = context(ShellPermissions::new());
+    let text = expand_in(&format!("[{{shell:{}}}]", command), "b", &context).unwrap();
+
+    assert_eq!(text, "[]");
+    assert!(!path.exists());
+    assert_eq!(context.take_errors().len(), 1);
+
+    // Without permissions, nothing may run.
+    let context = ExpansionContext::default();
+    expand_in("{shell:echo hi}", "b", &context).unwrap();
+    assert_eq!(context.take_errors().len(), 1);
+}
+
+#[test]
+fn reports_errors_instead_of_typing_them() {
+    let mut permissions = ShellPermissions::new();
+    permissions.allow("exit 3", None);
+
+    let context = context(permissions);
+    let text = expand_in("[{shell:exit 3}]", "b", &context).unwrap();
+
+    assert_eq!(text, "[]");
+
+    let errors = context.take_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("exit 3"));
+}
+
+#[test]
+fn stops_slow_commands() {
+    let settings = ShellSettings {
+        timeout: 100,
+        ..Default::default()
+    };
+
+    let started = Instant::now();
+    let result = run_command("sleep 5", &settings);
+
+    assert!(result.unwrap_err().contains("100 ms"));
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn limits_output_size() {
+    let settings = ShellSettings {
+        max_output: 10,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        run_command("printf 0123456789", &settings).unwrap(),
+        "0123456789"
+    );
+    assert!(run_command("yes", &settings)
+        .unwrap_err()
+        .contains("10 bytes"));
+}
+
+#[test]
+fn shell_needs_no_parameters() {
+    assert!(!has_variables("{shell:git branch --show-current}"));
+}
+
+fn command(text: &str) -> Token {
+    Token::Variable(Variable {
+        name: format!("shell:{}", text),
+        default: String::new(),
+        filters: vec![],
+        choices: vec![],
+        kind: None,
+    })
+}
+
+#[test]
+fn commands_take_everything_up_to_the_closing_brace() {
+    assert_eq!(
+        tokenize("{shell:git log -1 --format=%h}").unwrap(),
+        vec![command("git log -1 --format=%h")]
+    );
+    assert_eq!(
+        tokenize("[{shell:git branch | head -1}]").unwrap(),
+        vec![
+            Token::Text("[".to_string()),
+            command("git branch | head -1"),
+            Token::Text("]".to_string()),
+        ]
+    );
+    assert_eq!(
+        tokenize(r"{shell:awk '{print $1\}' a\\b}").unwrap(),
+        vec![command(r"awk '{print $1}' a\\b")]
+    );
+}
+
+#[test]
+fn runs_pipelines() {
+    let mut permissions = ShellPermissions::new();
+    permissions.allow("echo a=b | tr a x", None);
+
+    let context = context(permissions);
+    let text = expand_in("{shell:echo a=b | tr a x}", "b", &context).unwrap();
+
+    assert_eq!(text, "x=b");
+    assert!(context.take_errors().is_empty());
+}
@@ -0,0 +1,146 @@
+use typls_core::{
+    execute_caret_move, plan_expansion, AppSettings, ExpansionContext, ExpansionPlan, Key,
+    KeyEvent, OutputAction, PlanKey, RecordingSink, SessionUpdate,
+};
+
+mod common;
+
+use common::{settings_with, signal};
+
+fn settings() -> AppSettings {
+    settings_with("mail", "Dear {$1},\n{$2}\nBest, {|}")
+}
+
+fn plan(sequence: &str) -> ExpansionPlan {
+    plan_expansion(
+        &signal(sequence, ""),
+        &settings(),
+        &ExpansionContext::default(),
+    )
+    .unwrap()
+}
+
+fn type_text(update: &mut impl FnMut(KeyEvent) -> SessionUpdate, text: &str) {
+    for c in text.chars() {
+        assert_eq!(
+            update(KeyEvent::text(&c.to_string())),
+            SessionUpdate::Continue
+        );
+    }
+}
+
+#[test]
+fn places_caret_at_first_stop() {
+    let plan = plan("mail");
+
+    assert_eq!(plan.text, "Dear ,\n\nBest, ");
+    // The caret moves back from the end to right after "Dear ".
+    assert_eq!(plan.keys, vec![PlanKey::Left; 9]);
+    assert!(plan.snippet.is_some());
+}
+
+#[test]
+fn moves_to_next_stops() {
+    let mut session = plan("mail").snippet.unwrap();
+    let mut update = |event: KeyEvent| session.handle(&event, Key::Tab);
+
+    type_text(&mut update, "Peter");
+
+    // The tab typed by the hotkey is erased before moving on to {$2}.
+    let tab = KeyEvent {
+        key: Key::Tab,
+        name: Some("\t".to_string()),
+    };
+    let SessionUpdate::Move(caret_move) = update(tab.clone()) else {
+        panic!("expected a caret move");
+    };
+    assert_eq!(
+        (caret_move.erase, caret_move.left, caret_move.right),
+        (1, 0, 2)
+    );
+
+    type_text(&mut update, "Thanks!");
+
+    let SessionUpdate::Move(caret_move) = update(tab.clone()) else {
+        panic!("expected a caret move");
+    };
+    assert_eq!(
+        (caret_move.erase, caret_move.left, caret_move.right),
+        (1, 0, 7)
+    );
+
+    let mut sink = RecordingSink::new();
+    execute_caret_move(&caret_move, &mut sink).unwrap();
+    assert_eq!(sink.actions[0], OutputAction::Erase(1));
+    assert_eq!(sink.actions.len(), 8);
+
+    // All stops were visited.
+    assert_eq!(update(tab), SessionUpdate::End);
+}
+
+#[test]
+fn tracks_backspace_and_arrows() {
+    let mut session = plan("mail").snippet.unwrap();
+
+    for event in [
+        KeyEvent::text("P"),
+        KeyEvent::text("x"),
+        KeyEvent::key(Key::Backspace),
+        KeyEvent::key(Key::LeftArrow),
+        KeyEvent::key(Key::RightArrow),
+    ] {
+        assert_eq!(session.handle(&event, Key::Tab), SessionUpdate::Continue);
+    }
+
+    let SessionUpdate::Move(caret_move) = session.handle(&KeyEvent::key(Key::Tab), Key::Tab) else {
+        panic!("expected a caret move");
+    };
+    assert_eq!(
+        (caret_move.erase, caret_move.left, caret_move.right),
+        (0, 0, 2)
+    );
+}
+
+#[test]
+fn ends_on_unknown_caret_position() {
+    let mut session = plan("mail").snippet.unwrap();
+
+    assert_eq!(
+        session.handle(&KeyEvent::key(Key::Other), Key::Tab),
+        SessionUpdate::End
+    );
+}
+
+#[test]
+fn cursor_marker_alone_has_no_session() {
+    let mut settings = settings();
+    settings.expansions[1].text = "<b>{|}</b>".to_string();
+
+    let plan = plan_expansion(
+        &signal("mail", " "),
+        &settings,
+        &ExpansionContext::default(),
+    )
+    .unwrap();
+
+    assert_eq!(plan.text, "<b></b> ");
+    assert_eq!(plan.keys, vec![PlanKey::Left; 5]);
+    assert!(plan.snippet.is_none());
+}
+
+#[test]
+fn markers_in_values_stay_text() {
+    let mut settings = settings();
+    settings.expansions[1].text = "<{v}>{|}".to_string();
+
+    let plan = plan_expansion(
+        &signal("mail|v={$1}{$2}", ""),
+        &settings,
+        &ExpansionContext::default(),
+    )
+    .unwrap();
+
+    assert_eq!(plan.text, "<{$1}{$2}>");
+    assert!(plan.keys.is_empty());
+    assert!(plan.snippet.is_none());
+}
@@ -0,0 +1,56 @@
+use typls_core::{plan_expansion, AppSettings, ExpansionContext, UsageStats};
+
+mod common;
+
+use common::{settings_with, signal};
+
+fn settings() -> AppSettings {
+    let mut settings = settings_with("hi", "Hello {}!");
+    settings.expansions.last_mut().unwrap().id = "hi".to_string();
+    settings
+}
+
+fn record(stats: &mut UsageStats, settings: &AppSettings, sequence: &str, app: &str) {
+    let signal = signal(sequence, " ");
+    let plan = plan_expansion(&signal, settings, &ExpansionContext::default()).unwrap();
+
+    stats.record(&signal, &plan, app);
+}
+
+#[test]
+fn counts_characters_and_apps() {
+    let settings = settings();
+    let mut stats = UsageStats::new();
+
+    // Typed "'hi|Peter " (10), produced "Hello Peter! " (13).
+    record(&mut stats, &settings, "hi|Peter", "editor");
+    record(&mut stats, &settings, "hi|Peter", "mail");
+    record(&mut stats, &settings, "hi|Peter", "mail");
+
+    let summary = stats.summary(&settings);
+
+    assert_eq!(summary.count, 3);
+    assert_eq!(summary.chars_typed, 30);
+    assert_eq!(summary.chars_produced, 39);
+    assert_eq!(summary.chars_saved, 9);
+    assert_eq!(summary.apps["mail"], 2);
+    assert_eq!(summary.expansions.len(), 1);
+    assert_eq!(summary.expansions[0].abbr, "hi");
+    assert_eq!(summary.expansions[0].stats.apps["editor"], 1);
+}
+
+#[test]
+fn lists_unused_expansions() {
+    let settings = settings();
+    let mut stats = UsageStats::new();
+
+    record(&mut stats, &settings, "hi", "editor");
+
+    let unused = stats
+        .unused(&settings)
+        .iter()
+        .map(|expansion| expansion.abbr.as_str())
+        .collect::<Vec<_>>();
+
+    assert_eq!(unused, vec!["typls"]);
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use typls_core::{has_variables, ActiveApp, ExpansionContext, SystemSource};
+
+mod common;
+
+struct FakeSystem;
+
+impl SystemSource for FakeSystem {
+    fn clipboard(&self) -> Option<String> {
+        None
+    }
+
+    fn user_name(&self) -> Option<String> {
+        Some("anna".to_string())
+    }
+
+    fn host_name(&self) -> Option<String> {
+        Some("workstation".to_string())
+    }
+}
+
+fn expand_text(text: &str) -> String {
+    let context = ExpansionContext::new(ActiveApp {
+        process_path: "/usr/bin/firefox".to_string(),
+        platform: "linux".to_string(),
+        name: "Firefox".to_string(),
+        title: "Issue 42 - Tracker".to_string(),
+    })
+    .with_system(Arc::new(FakeSystem));
+
+    common::expand_in(text, "bug", &context).unwrap()
+}
+
+#[test]
+fn inserts_machine_information() {
+    assert_eq!(
+        expand_text("{system:user}@{system:host} ({system:os})"),
+        "anna@workstation (linux)"
+    );
+}
+
+#[test]
+fn inserts_focused_app() {
+    assert_eq!(
+        expand_text("{app} [{app:path}]: {app:title}"),
+        "Firefox [/usr/bin/firefox]: Issue 42 - Tracker"
+    );
+}
+
+#[test]
+fn inserts_environment_variables() {
+    std::env::set_var("TYPLS_TEST_TICKET", "OPS-7");
+
+    assert_eq!(
+        expand_text("Ticket {env:TYPLS_TEST_TICKET}"),
+        "Ticket OPS-7"
+    );
+    assert_eq!(expand_text("[{env:TYPLS_TEST_MISSING=none}]"), "[none]");
+}
+
+#[test]
+fn unknown_fields_use_default() {
+    assert_eq!(expand_text("{system:kernel=unknown}"), "unknown");
+    assert_eq!(expand_text("{app:icon=?}"), "?");
+}
+
+#[test]
+fn system_variables_need_no_parameters() {
+    assert!(!has_variables("{env:HOME} {system:host} {app:title}"));
+}
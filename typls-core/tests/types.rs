@@ -0,0 +1,104 @@
+use typls_core::{
+    plan_expansion, tokenize, CaptureSignal, ExpansionContext, Token, Variable, VariableType,
+};
+
+mod common;
+
+use common::expand_with_errors;
+
+#[test]
+fn accepts_values_of_the_type() {
+    let cases = [
+        ("{count:int} items", "t|count=-3", "-3 items"),
+        ("Due {when:date}", "t|when=2024-05-17", "Due 2024-05-17"),
+        (
+            "<{to:email}>",
+            "t|to=anna@example.com",
+            "<anna@example.com>",
+        ),
+        ("{id:/[A-Z]{3}-\\d+/}", "t|id=OPS-42", "OPS-42"),
+        ("{n:int=1}x", "t", "1x"),
+    ];
+
+    for (text, sequence, expected) in cases {
+        let (result, errors) = expand_with_errors(text, sequence);
+        assert_eq!(result.as_deref(), Some(expected), "{:?}", errors);
+    }
+}
+
+#[test]
+fn aborts_on_invalid_values() {
+    let cases = [
+        (
+            "{count:int}",
+            "t|count=many",
+            "`count` needs a whole number, got `many`",
+        ),
+        ("{when:date}", "t|when=2024-13-01", "a date like 2024-05-17"),
+        ("{to:email}", "t|to=anna@localhost", "an email address"),
+        ("{id:/[A-Z]{3}-\\d+/}", "t|id=ops-42", "`/[A-Z]{3}-\\d+/`"),
+        ("{count:int}", "t", "`count` needs a whole number"),
+    ];
+
+    for (text, sequence, error) in cases {
+        let (result, errors) = expand_with_errors(text, sequence);
+
+        assert_eq!(result, None, "{}", sequence);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains(error), "{}", errors[0]);
+    }
+}
+
+#[test]
+fn leaves_abbreviation_on_invalid_values() {
+    let settings = common::settings_with("t", "{count:int}");
+
+    let context = ExpansionContext::default();
+    let signal = CaptureSignal {
+        sequence: "t|count=x".to_string(),
+        append: " ".to_string(),
+        append_enter: false,
+    };
+
+    assert_eq!(plan_expansion(&signal, &settings, &context), None);
+    assert_eq!(context.take_errors().len(), 1);
+}
+
+#[test]
+fn rejects_invalid_defaults_and_patterns() {
+    assert!(tokenize("{n:int=one}").is_err_and(|e| e.contains("{n:int=one}")));
+    assert!(tokenize("{id:/[A-/}").is_err_and(|e| e.contains("Invalid pattern")));
+}
+
+#[test]
+fn parses_types() {
+    let tokens = tokenize("{id:/a\\/b|c{2}/=a/b} {tone:int|email}").unwrap();
+
+    let Token::Variable(id) = &tokens[0] else {
+        panic!("{:?}", tokens);
+    };
+    assert_eq!(
+        id.kind,
+        Some(VariableType::Pattern("a\\/b|c{2}".to_string()))
+    );
+    assert_eq!(id.default, "a/b");
+
+    // More than one option is a choice, even if they are type names.
+    let Token::Variable(tone) = &tokens[2] else {
+        panic!("{:?}", tokens);
+    };
+    assert_eq!(tone.kind, None);
+    assert_eq!(tone.choices, vec!["int", "email"]);
+
+    // Arguments of built-in variables and paths are no patterns.
+    assert_eq!(
+        tokenize("{date:/%Y/} {path:/usr/bin}").unwrap()[2],
+        Token::Variable(Variable {
+            name: "path".to_string(),
+            default: "/usr/bin".to_string(),
+            filters: vec![],
+            choices: vec!["/usr/bin".to_string()],
+            kind: None,
+        })
+    );
+}
@@ -0,0 +1,93 @@
+use typls_core::{
+    execute_undo, plan_expansion, AppSettings, ExpansionContext, ExpansionFormat, Key, KeyEvent,
+    LastExpansion, OutputAction, PlanKey, RecordingSink, UndoSettings, UndoWindow,
+};
+
+mod common;
+
+use common::{settings_with_all, signal};
+
+fn settings() -> AppSettings {
+    settings_with_all(&[("hi", "Hello {}!"), ("div", "<div>{|}</div>")])
+}
+
+fn open_window(sequence: &str) -> UndoWindow {
+    let settings = settings();
+    let signal = signal(sequence, " ");
+    let plan = plan_expansion(&signal, &settings, &ExpansionContext::default()).unwrap();
+
+    let mut window = UndoWindow::new();
+    window.open(LastExpansion::new(&signal, &plan, &settings.trigger.string, "window").unwrap());
+    window
+}
+
+#[test]
+fn backspace_undoes_expansion() {
+    let mut window = open_window("hi|Peter");
+
+    let undo = window
+        .handle(&KeyEvent::key(Key::Backspace), &UndoSettings::default())
+        .unwrap();
+
+    // "Hello Peter! " minus the character the backspace removed.
+    assert_eq!(undo.right, 0);
+    assert_eq!(undo.erase, 12);
+    assert_eq!(undo.text, "'hi|Peter");
+    assert!(!window.is_open());
+}
+
+#[test]
+fn hotkey_undoes_expansion_with_cursor() {
+    let mut window = open_window("div");
+    let settings = UndoSettings {
+        backspace: false,
+        key: Some(Key::Function(9)),
+    };
+
+    assert_eq!(
+        window.handle(&KeyEvent::key(Key::Modifier), &settings),
+        None
+    );
+
+    let undo = window
+        .handle(&KeyEvent::key(Key::Function(9)), &settings)
+        .unwrap();
+
+    let mut sink = RecordingSink::new();
+    execute_undo(&undo, &mut sink).unwrap();
+
+    let mut expected = vec![OutputAction::Press(PlanKey::Right); 7];
+    expected.push(OutputAction::Erase(12));
+    expected.push(OutputAction::Text("'div".to_string()));
+    assert_eq!(sink.actions, expected);
+}
+
+#[test]
+fn other_keys_close_window() {
+    let mut window = open_window("hi");
+
+    assert_eq!(
+        window.handle(&KeyEvent::text("a"), &UndoSettings::default()),
+        None
+    );
+    assert_eq!(
+        window.handle(&KeyEvent::key(Key::Backspace), &UndoSettings::default()),
+        None
+    );
+}
+
+#[test]
+fn formatted_expansions_can_not_be_undone() {
+    let mut settings = settings();
+    for expansion in &mut settings.expansions {
+        expansion.format = ExpansionFormat::Markdown;
+    }
+
+    let signal = signal("hi|Peter", " ");
+    let plan = plan_expansion(&signal, &settings, &ExpansionContext::default()).unwrap();
+
+    assert_eq!(
+        LastExpansion::new(&signal, &plan, &settings.trigger.string, "window"),
+        None
+    );
+}